@@ -0,0 +1,327 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    http::Method,
+    routing::{get, patch, post},
+};
+use tower_http::{
+    cors::{Any, CorsLayer},
+    trace::TraceLayer,
+};
+use tracing::{info, warn};
+
+use crate::{
+    database::{database, migrations, schema},
+    handlers::{
+        address_handler::{
+            handler_create_address, handler_deposit_coin, handler_generate_address,
+            handler_get_address,
+        },
+        block_handler::{
+            handler_build_block, handler_get_block_by_hash, handler_get_latest_block,
+            handler_get_total_supply, handler_get_transaction_proof,
+            handler_verify_block_signatures, handler_verify_chain,
+        },
+        mempool_handler::handler_get_mempool_stats,
+        transaction_handler::{
+            handler_confirm_transaction, handler_create_transaction,
+            handler_get_pending_transactions, handler_get_transaction_by_id,
+            handler_get_transactions_by_address,
+        },
+    },
+    repository::{
+        address_repository::{
+            MemoryAddressRepository, MongoAddressRepository, RedbAddressRepository,
+            SharedAddressRepository,
+        },
+        block_repository::{
+            MemoryBlockRepository, MongoBlockRepository, RedbBlockRepository, SharedBlockRepository,
+        },
+        memory_store::{MemorySessionFactory, MemoryStore},
+        redb_store::{RedbSessionFactory, RedbStore},
+        session::{MongoSessionFactory, SharedSessionFactory},
+        transaction_repository::{
+            MemoryTransactionRepository, MongoTransactionRepository, RedbTransactionRepository,
+            SharedTransactionRepository,
+        },
+    },
+    setting::{Development, Setting, StorageBackend},
+    timer_helper::{IntoTimerHelperShared, TimerHelper},
+    usecases::{
+        address_usecase::AddressUsecase, block_usecase::BlockUsecase,
+        mempool_usecase::MempoolUsecase, transaction_usecase::TransactionUsecase,
+    },
+};
+
+/// The repositories of one storage backend.
+pub struct Repositories {
+    pub address: SharedAddressRepository,
+    pub transaction: SharedTransactionRepository,
+    pub block: SharedBlockRepository,
+    pub session_factory: SharedSessionFactory,
+}
+
+impl Repositories {
+    /// Opens the backend chosen by `storage.backend`.
+    pub async fn open(setting: Arc<Setting>) -> Result<Self, String> {
+        return match setting.storage.backend {
+            StorageBackend::Mongo => {
+                let db = database::db_connect(Arc::clone(&setting))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                info!("database connect successfully");
+                if setting.database.migrate_on_startup {
+                    migrations::migrate(&db, false, TimerHelper::Directly.creation()).await?;
+                } else {
+                    let pending = migrations::pending(&db).await?;
+                    if !pending.is_empty() {
+                        return Err(format!(
+                            "{} pending migrations, run `rust_chain migrate`",
+                            pending.len()
+                        ));
+                    }
+                }
+                schema::bootstrap(&db, &setting).await?;
+
+                Ok(Self {
+                    address: MongoAddressRepository::creation(db.clone()),
+                    transaction: MongoTransactionRepository::creation(db.clone()),
+                    block: MongoBlockRepository::creation(db.clone(), Arc::clone(&setting)),
+                    session_factory: MongoSessionFactory::creation(db),
+                })
+            }
+            StorageBackend::Memory => {
+                warn!("storage.backend is memory, nothing is persisted");
+                Ok(Self::memory(MemoryStore::creation(), setting))
+            }
+            StorageBackend::Redb => {
+                let store = RedbStore::open(&setting.storage.path)?;
+                info!("redb database {} opened", setting.storage.path);
+                Ok(Self::redb(store, setting))
+            }
+        };
+    }
+
+    pub fn memory(store: Arc<MemoryStore>, setting: Arc<Setting>) -> Self {
+        return Self {
+            address: MemoryAddressRepository::creation(Arc::clone(&store)),
+            transaction: MemoryTransactionRepository::creation(Arc::clone(&store)),
+            block: MemoryBlockRepository::creation(Arc::clone(&store), setting),
+            session_factory: MemorySessionFactory::creation(store),
+        };
+    }
+
+    pub fn redb(store: Arc<RedbStore>, setting: Arc<Setting>) -> Self {
+        return Self {
+            address: RedbAddressRepository::creation(Arc::clone(&store)),
+            transaction: RedbTransactionRepository::creation(Arc::clone(&store)),
+            block: RedbBlockRepository::creation(Arc::clone(&store), setting),
+            session_factory: RedbSessionFactory::creation(store),
+        };
+    }
+}
+
+/// Wires the usecases over `repositories` and returns the HTTP routes.
+/// Starts the mempool sweeper, so it must run inside a tokio runtime.
+pub fn router(
+    setting: Arc<Setting>,
+    repositories: Repositories,
+    timer_helper: IntoTimerHelperShared,
+) -> Router {
+    let address_usecase = AddressUsecase::creation(
+        Arc::clone(&repositories.address),
+        Arc::clone(&setting),
+        Arc::clone(&timer_helper),
+    );
+
+    let mempool_usecase = MempoolUsecase::creation(
+        Arc::clone(&repositories.transaction),
+        Arc::clone(&setting),
+        Arc::clone(&timer_helper),
+    );
+    mempool_usecase.spawn_sweeper();
+    let transaction_usecase = TransactionUsecase::creation(
+        Arc::clone(&repositories.transaction),
+        Arc::clone(&repositories.address),
+        Arc::clone(&mempool_usecase),
+        Arc::clone(&repositories.session_factory),
+        Arc::clone(&setting),
+        Arc::clone(&timer_helper),
+    );
+
+    let block_usecase = BlockUsecase::creation(
+        Arc::clone(&repositories.block),
+        Arc::clone(&transaction_usecase),
+        Arc::clone(&address_usecase),
+        Arc::clone(&mempool_usecase),
+        Arc::clone(&repositories.session_factory),
+        Arc::clone(&setting),
+        Arc::clone(&timer_helper),
+    );
+
+    return Router::new()
+        .layer(
+            CorsLayer::new()
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                ])
+                .allow_origin(Any),
+        )
+        .layer(TraceLayer::new_for_http())
+        .merge(address_routes(
+            Arc::clone(&address_usecase),
+            &setting.development,
+        ))
+        .merge(transaction_routes(Arc::clone(&transaction_usecase)))
+        .merge(block_routes(Arc::clone(&block_usecase)))
+        .merge(mempool_routes(Arc::clone(&mempool_usecase)));
+}
+
+fn address_routes(address_usecase: Arc<AddressUsecase>, development: &Development) -> Router {
+    let mut router = Router::<()>::new()
+        .route(
+            "/addresses",
+            post({
+                let usecase = Arc::clone(&address_usecase);
+                move |body| handler_create_address(body, usecase)
+            }),
+        )
+        .route(
+            "/addresses/{address}",
+            get({
+                let usecase = Arc::clone(&address_usecase);
+                move |path| handler_get_address(path, usecase)
+            }),
+        );
+
+    if development.faucet {
+        warn!("development.faucet is on, PATCH /addresses/{{address}} mints coins off-chain");
+        router = router.route(
+            "/addresses/{address}",
+            patch({
+                let usecase = Arc::clone(&address_usecase);
+                move |path, body| handler_deposit_coin(path, body, usecase)
+            }),
+        );
+    }
+
+    if !development.generate_keys {
+        return router;
+    }
+
+    warn!("development.generate_keys is on, POST /addresses/generate returns secret keys");
+    return router.route(
+        "/addresses/generate",
+        post({
+            let usecase = Arc::clone(&address_usecase);
+            move || handler_generate_address(usecase)
+        }),
+    );
+}
+
+fn transaction_routes(transaction_usecase: Arc<TransactionUsecase>) -> Router {
+    return Router::<()>::new()
+        .route(
+            "/transactions",
+            post({
+                let usecase = Arc::clone(&transaction_usecase);
+                move |body| handler_create_transaction(body, usecase)
+            }),
+        )
+        .route(
+            "/transactions/{id}",
+            get({
+                let usecase = Arc::clone(&transaction_usecase);
+                move |path| handler_get_transaction_by_id(path, usecase)
+            }),
+        )
+        .route(
+            "/addresses/{address}/transactions",
+            get({
+                let usecase = Arc::clone(&transaction_usecase);
+                move |path| handler_get_transactions_by_address(path, usecase)
+            }),
+        )
+        .route(
+            "/transactions/pending",
+            get({
+                let usecase = Arc::clone(&transaction_usecase);
+                move || handler_get_pending_transactions(usecase)
+            }),
+        )
+        .route(
+            "/transactions/{id}/confirm",
+            patch({
+                let usecase = Arc::clone(&transaction_usecase);
+                move |path, body| handler_confirm_transaction(path, body, usecase)
+            }),
+        );
+}
+
+fn block_routes(block_usecase: Arc<BlockUsecase>) -> Router {
+    return Router::<()>::new()
+        .route(
+            "/blocks",
+            post({
+                let usecase = Arc::clone(&block_usecase);
+                move || handler_build_block(usecase)
+            }),
+        )
+        .route(
+            "/blocks/latest",
+            get({
+                let usecase = Arc::clone(&block_usecase);
+                move || handler_get_latest_block(usecase)
+            }),
+        )
+        .route(
+            "/blocks/{hash}",
+            get({
+                let usecase = Arc::clone(&block_usecase);
+                move |path| handler_get_block_by_hash(path, usecase)
+            }),
+        )
+        .route(
+            "/blocks/supply",
+            get({
+                let usecase = Arc::clone(&block_usecase);
+                move || handler_get_total_supply(usecase)
+            }),
+        )
+        .route(
+            "/blocks/verify",
+            get({
+                let usecase = Arc::clone(&block_usecase);
+                move || handler_verify_chain(usecase)
+            }),
+        )
+        .route(
+            "/blocks/{hash}/signatures",
+            get({
+                let usecase = Arc::clone(&block_usecase);
+                move |path| handler_verify_block_signatures(path, usecase)
+            }),
+        )
+        .route(
+            "/transactions/{id}/proof",
+            get({
+                let usecase = Arc::clone(&block_usecase);
+                move |path| handler_get_transaction_proof(path, usecase)
+            }),
+        );
+}
+
+fn mempool_routes(mempool_usecase: Arc<MempoolUsecase>) -> Router {
+    return Router::<()>::new().route(
+        "/mempool",
+        get({
+            let usecase = Arc::clone(&mempool_usecase);
+            move || handler_get_mempool_stats(usecase)
+        }),
+    );
+}
@@ -0,0 +1,324 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Router,
+        body::{self, Body},
+        http::{Method, Request, StatusCode},
+    };
+    use bson::oid::ObjectId;
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    use crate::{
+        app::{Repositories, router},
+        crypto_helper,
+        entities::transaction_entity::TransactionStatus,
+        models::{
+            address_model::InsertAddress,
+            transaction_model::{CreateTransactionRequest, SignatureScheme, TransactionPayload},
+        },
+        repository::{memory_store::MemoryStore, redb_store::RedbStore},
+        setting::Setting,
+        timer_helper::TimerHelper,
+    };
+
+    // mining.miner_address is set to the address of secret key 3
+    const MINER_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000003";
+    const MINER_ADDRESS: &str = "RLkZJhcqT2ac31oqBeLvo6egULQR54LNxb";
+    const SENDER_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+    fn setting() -> Arc<Setting> {
+        let toml = include_str!("../Settings.toml")
+            .replace("backend = \"mongo\"", "backend = \"memory\"")
+            .replace("difficulty = 16", "difficulty = 1")
+            .replace(
+                "miner_address = \"\"",
+                &format!("miner_address = \"{}\"", MINER_ADDRESS),
+            );
+        return Setting::from_toml(&toml).unwrap();
+    }
+
+    fn app() -> Router {
+        let setting = setting();
+        let repositories = Repositories::memory(MemoryStore::creation(), Arc::clone(&setting));
+        return router(setting, repositories, TimerHelper::Directly.creation());
+    }
+
+    async fn call(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(match body {
+                Some(body) => Body::from(body.to_string()),
+                None => Body::empty(),
+            })
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        return (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        );
+    }
+
+    async fn register(app: &Router, secret_key: &str) -> String {
+        let public_key = crypto_helper::public_key_from_secret_key(secret_key).unwrap();
+        let signature = crypto_helper::sign_registration(1, &public_key, secret_key).unwrap();
+        let body = json!(InsertAddress {
+            public_key,
+            signature
+        });
+
+        let (status, body) = call(app, Method::POST, "/addresses", Some(body)).await;
+        assert!(status.is_success(), "{} {}", status, body);
+        return body["address"].as_str().unwrap().to_string();
+    }
+
+    async fn balance(app: &Router, address: &str) -> u64 {
+        let (_, body) = call(app, Method::GET, &format!("/addresses/{}", address), None).await;
+        return body["address"]["balance"].as_u64().unwrap();
+    }
+
+    async fn build_block(app: &Router) -> String {
+        let (status, body) = call(app, Method::POST, "/blocks", None).await;
+        assert!(status.is_success(), "{} {}", status, body);
+        let (_, body) = call(app, Method::GET, "/blocks/latest", None).await;
+        return body["block"]["hash"].as_str().unwrap().to_string();
+    }
+
+    /// Signs and submits a transfer from the address of `secret_key`, paying
+    /// a fee of 1.
+    async fn transfer(
+        app: &Router,
+        secret_key: &str,
+        to: &str,
+        amount: u64,
+        nonce: u64,
+    ) -> (StatusCode, Value) {
+        let public_key = crypto_helper::public_key_from_secret_key(secret_key).unwrap();
+        let from = crypto_helper::address_from_public_key(&public_key).unwrap();
+        let payload = TransactionPayload {
+            chain_id: 1,
+            from: from.clone(),
+            to: to.to_string(),
+            amount,
+            fee: 1,
+            nonce,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+        };
+        let request = CreateTransactionRequest {
+            from,
+            to: to.to_string(),
+            amount,
+            fee: 1,
+            nonce,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+            public_key,
+            signature: crypto_helper::sign_payload(&payload, secret_key).unwrap(),
+        };
+        return call(app, Method::POST, "/transactions", Some(json!(request))).await;
+    }
+
+    async fn transfer_end_to_end(app: Router) {
+        let miner = register(&app, MINER_KEY).await;
+        let sender = register(&app, SENDER_KEY).await;
+        build_block(&app).await;
+        assert_eq!(balance(&app, &miner).await, 50);
+
+        let payload = TransactionPayload {
+            chain_id: 1,
+            from: miner.clone(),
+            to: sender.clone(),
+            amount: 10,
+            fee: 1,
+            nonce: 0,
+            valid_until: None,
+            scheme: SignatureScheme::Schnorr,
+        };
+        let request = CreateTransactionRequest {
+            from: miner.clone(),
+            to: sender.clone(),
+            amount: 10,
+            fee: 1,
+            nonce: 0,
+            valid_until: None,
+            scheme: SignatureScheme::Schnorr,
+            public_key: crypto_helper::public_key_from_secret_key(MINER_KEY).unwrap(),
+            signature: crypto_helper::sign_payload(&payload, MINER_KEY).unwrap(),
+        };
+        let (status, body) = call(&app, Method::POST, "/transactions", Some(json!(request))).await;
+        assert!(status.is_success(), "{} {}", status, body);
+
+        let hash = build_block(&app).await;
+        assert_eq!(balance(&app, &sender).await, 10);
+        assert_eq!(balance(&app, &miner).await, 50 - 11 + 50 + 1);
+
+        let (_, body) = call(&app, Method::GET, "/blocks/verify", None).await;
+        assert_eq!(body["report"]["valid"], json!(true));
+        assert_eq!(body["report"]["checked_blocks"], json!(2));
+
+        let uri = format!("/blocks/{}/signatures", hash);
+        let (_, body) = call(&app, Method::GET, &uri, None).await;
+        assert_eq!(body["report"]["checked"], json!(1));
+        assert_eq!(body["report"]["failed"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn transfer_end_to_end_test() {
+        transfer_end_to_end(app()).await;
+    }
+
+    #[tokio::test]
+    async fn transfer_end_to_end_on_redb_test() {
+        let path = std::env::temp_dir().join(format!("rust_chain_app_{}.redb", ObjectId::new()));
+        let setting = setting();
+        let repositories =
+            Repositories::redb(RedbStore::open(&path).unwrap(), Arc::clone(&setting));
+
+        transfer_end_to_end(router(
+            setting,
+            repositories,
+            TimerHelper::Directly.creation(),
+        ))
+        .await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn nonce_gap_stays_pending_test() {
+        let setting = setting();
+        let repositories = Repositories::memory(MemoryStore::creation(), Arc::clone(&setting));
+        let txs = Arc::clone(&repositories.transaction);
+        let app = router(setting, repositories, TimerHelper::Directly.creation());
+        let miner = register(&app, MINER_KEY).await;
+        let sender = register(&app, SENDER_KEY).await;
+        build_block(&app).await;
+
+        for nonce in [0, 1] {
+            let (status, body) = transfer(&app, MINER_KEY, &sender, 10, nonce).await;
+            assert!(status.is_success(), "{} {}", status, body);
+        }
+        // the sweeper expires nonce 0
+        let pending = txs.find_pending_by_sender(miner.clone()).await.unwrap();
+        let first = pending.iter().find(|tx| tx.nonce == 0).unwrap();
+        txs.update_status(first.id.unwrap(), TransactionStatus::Expired)
+            .await
+            .unwrap();
+
+        build_block(&app).await;
+        let pending = txs.find_pending_by_sender(miner.clone()).await.unwrap();
+        assert_eq!(
+            pending.iter().map(|tx| tx.nonce).collect::<Vec<u64>>(),
+            vec![1]
+        );
+
+        // sending nonce 0 again unblocks nonce 1
+        let (status, body) = transfer(&app, MINER_KEY, &sender, 10, 0).await;
+        assert!(status.is_success(), "{} {}", status, body);
+        build_block(&app).await;
+        assert_eq!(balance(&app, &sender).await, 20);
+        assert_eq!(txs.find_pending_by_sender(miner).await, Ok(vec![]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_transfers_spend_once_test() {
+        let setting = setting();
+        let repositories = Repositories::memory(MemoryStore::creation(), Arc::clone(&setting));
+        let txs = Arc::clone(&repositories.transaction);
+        let app = router(setting, repositories, TimerHelper::Directly.creation());
+        let miner = register(&app, MINER_KEY).await;
+        let sender = register(&app, SENDER_KEY).await;
+        build_block(&app).await;
+
+        // both spend nonce 0 and most of the 50 coins
+        let (first, second) = tokio::join!(
+            transfer(&app, MINER_KEY, &sender, 40, 0),
+            transfer(&app, MINER_KEY, &sender, 45, 0),
+        );
+        assert_ne!(first.0.is_success(), second.0.is_success());
+        assert_eq!(txs.find_pending_by_sender(miner).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn faucet_test() {
+        let body = Some(json!({ "amount": 5 }));
+        let app = app();
+        let sender = register(&app, SENDER_KEY).await;
+        let uri = format!("/addresses/{}", sender);
+
+        let (status, _) = call(&app, Method::PATCH, &uri, body.clone()).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(balance(&app, &sender).await, 0);
+
+        let setting = Setting::from_toml(
+            &include_str!("../Settings.toml")
+                .replace("backend = \"mongo\"", "backend = \"memory\"")
+                .replace("faucet = false", "faucet = true"),
+        )
+        .unwrap();
+        let repositories = Repositories::memory(MemoryStore::creation(), Arc::clone(&setting));
+        let app = router(setting, repositories, TimerHelper::Directly.creation());
+        let sender = register(&app, SENDER_KEY).await;
+
+        let (status, _) = call(&app, Method::PATCH, &uri, body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(balance(&app, &sender).await, 5);
+    }
+
+    #[tokio::test]
+    async fn miner_address_required_test() {
+        let toml =
+            include_str!("../Settings.toml").replace("backend = \"mongo\"", "backend = \"memory\"");
+        let setting = Setting::from_toml(&toml).unwrap();
+        assert_eq!(setting.mining.miner_address, None);
+        let repositories = Repositories::memory(MemoryStore::creation(), Arc::clone(&setting));
+        let app = router(setting, repositories, TimerHelper::Directly.creation());
+
+        let (status, body) = call(&app, Method::POST, "/blocks", None).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(
+            body.to_string().contains("Miner address is not set"),
+            "{}",
+            body
+        );
+
+        let toml = toml.replace("miner_address = \"\"", "miner_address = \"R123\"");
+        assert!(Setting::from_toml(&toml).is_err());
+    }
+
+    #[tokio::test]
+    async fn duplicate_address_test() {
+        let app = app();
+        register(&app, SENDER_KEY).await;
+
+        let public_key = crypto_helper::public_key_from_secret_key(SENDER_KEY).unwrap();
+        let signature = crypto_helper::sign_registration(1, &public_key, SENDER_KEY).unwrap();
+        let body = json!(InsertAddress {
+            public_key,
+            signature
+        });
+        let (status, _) = call(&app, Method::POST, "/addresses", Some(body)).await;
+
+        assert!(!status.is_success());
+    }
+
+    #[tokio::test]
+    async fn unknown_block_test() {
+        let (status, _) = call(&app(), Method::GET, "/blocks/00", None).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use rust_chain::{
+    crypto_helper,
+    entities::{
+        address_entity::AddressEntity,
+        transaction_entity::{TransactionEntity, TransactionStatus},
+    },
+    keystore_helper::{self, Keystore},
+    models::{
+        address_model::InsertAddress,
+        transaction_model::{CreateTransactionRequest, SignatureScheme, TransactionPayload},
+    },
+    usecases::transaction_usecase,
+};
+use secp256k1::rand::rngs::OsRng;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+/// Keys owned by this wallet, each encrypted with the wallet password.
+#[derive(Default, Serialize, Deserialize)]
+struct WalletFile {
+    keys: Vec<Keystore>,
+}
+
+#[derive(Parser)]
+#[command(about = "Manage keys and send transactions to a rust_chain node")]
+struct Cli {
+    /// Wallet file holding the encrypted keys.
+    #[arg(long, default_value = "wallet.json")]
+    wallet: PathBuf,
+    /// Base URL of the node.
+    #[arg(long, default_value = "http://localhost:80")]
+    node: String,
+    /// Chain id signed into registrations and transactions.
+    #[arg(long, default_value_t = 1)]
+    chain_id: u64,
+    /// Seconds a transfer may stay pending, the node's
+    /// `mempool.transaction_ttl`.
+    #[arg(long, default_value_t = 3600)]
+    transaction_ttl: i64,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a key and store it in the wallet.
+    New {
+        /// Also register the new address with the node.
+        #[arg(long)]
+        register: bool,
+    },
+    /// List the addresses in the wallet.
+    List,
+    /// Re-encrypt every key in the wallet under a new password.
+    Passwd,
+    /// Register an owned address with the node.
+    Register { address: String },
+    /// Show the balance of an owned address, or of all of them.
+    Balance { address: Option<String> },
+    /// Show the transactions of an owned address.
+    History { address: String },
+    /// Sign and submit a transfer.
+    Send {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 0)]
+        fee: u64,
+        /// Unix timestamp after which the transfer can no longer be mined.
+        #[arg(long)]
+        valid_until: Option<i64>,
+        /// Sign with BIP340 Schnorr instead of ECDSA.
+        #[arg(long)]
+        schnorr: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let client = NodeClient {
+        http: reqwest::Client::new(),
+        base: cli.node.trim_end_matches('/').to_string(),
+    };
+
+    return match cli.command {
+        Command::New { register } => {
+            let mut wallet = load_wallet(&cli.wallet)?;
+            let password = wallet_password(&wallet)?;
+
+            let (secret_key, _) = crypto_helper::SECP256K1.generate_keypair(&mut OsRng);
+            let secret_key = hex::encode(secret_key.secret_bytes());
+            let keystore = keystore_helper::create(
+                &secret_key,
+                &password,
+                keystore_helper::PBKDF2_ITERATIONS,
+            )?;
+            wallet.keys.push(keystore.clone());
+            save_wallet(&cli.wallet, &wallet)?;
+            println!("{}", keystore.address);
+
+            if register {
+                register_address(&client, &keystore, &secret_key, cli.chain_id).await?;
+            }
+            Ok(())
+        }
+        Command::List => {
+            for keystore in load_wallet(&cli.wallet)?.keys {
+                println!("{}", keystore.address);
+            }
+            Ok(())
+        }
+        Command::Passwd => {
+            let mut wallet = load_wallet(&cli.wallet)?;
+            let old_password = password()?;
+            let password = new_password("WALLET_NEW_PASSWORD")?;
+            wallet.keys = wallet
+                .keys
+                .iter()
+                .map(|k| keystore_helper::change_password(k, &old_password, &password))
+                .collect::<Result<_, _>>()?;
+            save_wallet(&cli.wallet, &wallet)?;
+            println!("re-encrypted {} keys", wallet.keys.len());
+            Ok(())
+        }
+        Command::Register { address } => {
+            let wallet = load_wallet(&cli.wallet)?;
+            let keystore = owned_key(&wallet, &address)?;
+            let secret_key = keystore_helper::unlock(keystore, &password()?)?;
+            register_address(&client, keystore, &secret_key, cli.chain_id).await
+        }
+        Command::Balance { address } => {
+            let wallet = load_wallet(&cli.wallet)?;
+            let addresses = match address {
+                Some(address) => vec![owned_key(&wallet, &address)?.address.clone()],
+                None => wallet.keys.iter().map(|k| k.address.clone()).collect(),
+            };
+            for address in addresses {
+                match client.address(&address).await {
+                    Ok(account) => println!("{} {}", address, account.balance),
+                    Err(e) => println!("{} unavailable: {}", address, e),
+                }
+            }
+            Ok(())
+        }
+        Command::History { address } => {
+            let wallet = load_wallet(&cli.wallet)?;
+            owned_key(&wallet, &address)?;
+            for tx in client.transactions(&address).await? {
+                let direction = if tx.from == address { "out" } else { "in" };
+                println!(
+                    "{} {:?} {} {} -> {} amount {} fee {} nonce {}",
+                    tx.timestamp, tx.status, direction, tx.from, tx.to, tx.amount, tx.fee, tx.nonce
+                );
+            }
+            Ok(())
+        }
+        Command::Send {
+            from,
+            to,
+            amount,
+            fee,
+            valid_until,
+            schnorr,
+        } => {
+            let wallet = load_wallet(&cli.wallet)?;
+            let keystore = owned_key(&wallet, &from)?;
+            let secret_key = keystore_helper::unlock(keystore, &password()?)?;
+            let nonce = client.next_nonce(&from, cli.transaction_ttl).await?;
+
+            let scheme = if schnorr {
+                SignatureScheme::Schnorr
+            } else {
+                SignatureScheme::Ecdsa
+            };
+            let payload = TransactionPayload {
+                chain_id: cli.chain_id,
+                from: from.clone(),
+                to: to.clone(),
+                amount,
+                fee,
+                nonce,
+                valid_until,
+                scheme,
+            };
+            let signature =
+                crypto_helper::sign_payload(&payload, &secret_key).map_err(|e| e.to_string())?;
+
+            let request = CreateTransactionRequest {
+                from,
+                to,
+                amount,
+                fee,
+                nonce,
+                valid_until,
+                scheme,
+                public_key: keystore.public_key.clone(),
+                signature,
+            };
+            let response = client.post("/transactions", &request).await?;
+            println!("{}", response["object_id"]);
+            Ok(())
+        }
+    };
+}
+
+fn load_wallet(path: &PathBuf) -> Result<WalletFile, String> {
+    if !path.exists() {
+        return Ok(WalletFile::default());
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("read {:?}: {}", path, e))?;
+    return serde_json::from_str(&text).map_err(|e| format!("parse {:?}: {}", path, e));
+}
+
+fn save_wallet(path: &Path, wallet: &WalletFile) -> Result<(), String> {
+    return keystore_helper::write_file(path, wallet);
+}
+
+fn owned_key<'a>(wallet: &'a WalletFile, address: &str) -> Result<&'a Keystore, String> {
+    return wallet
+        .keys
+        .iter()
+        .find(|k| k.address == address)
+        .ok_or_else(|| format!("address {} is not in the wallet", address));
+}
+
+/// Reads the wallet password from `WALLET_PASSWORD` or the terminal.
+fn password() -> Result<String, String> {
+    if let Ok(password) = std::env::var("WALLET_PASSWORD") {
+        return Ok(password);
+    }
+    return rpassword::prompt_password("wallet password: ").map_err(|e| e.to_string());
+}
+
+/// The password to encrypt a new key with. A wallet that already holds
+/// keys keeps one password, checked by unlocking its first key, an empty
+/// one gets a new password.
+fn wallet_password(wallet: &WalletFile) -> Result<String, String> {
+    let Some(keystore) = wallet.keys.first() else {
+        return new_password("WALLET_PASSWORD");
+    };
+    let password = password()?;
+    keystore_helper::unlock(keystore, &password)?;
+    return Ok(password);
+}
+
+/// Reads a password to encrypt with from `env` or the terminal, asking for
+/// it twice.
+fn new_password(env: &str) -> Result<String, String> {
+    if let Ok(password) = std::env::var(env) {
+        return Ok(password);
+    }
+    let password =
+        rpassword::prompt_password("new wallet password: ").map_err(|e| e.to_string())?;
+    let confirm = rpassword::prompt_password("repeat password: ").map_err(|e| e.to_string())?;
+    if password != confirm {
+        return Err("passwords do not match".to_string());
+    }
+    return Ok(password);
+}
+
+async fn register_address(
+    client: &NodeClient,
+    keystore: &Keystore,
+    secret_key: &str,
+    chain_id: u64,
+) -> Result<(), String> {
+    let signature = crypto_helper::sign_registration(chain_id, &keystore.public_key, secret_key)
+        .map_err(|e| e.to_string())?;
+
+    client
+        .post(
+            "/addresses",
+            &InsertAddress {
+                public_key: keystore.public_key.clone(),
+                signature,
+            },
+        )
+        .await?;
+    println!("registered {}", keystore.address);
+    return Ok(());
+}
+
+struct NodeClient {
+    http: reqwest::Client,
+    base: String,
+}
+
+impl NodeClient {
+    async fn get<T: DeserializeOwned>(&self, path: &str, field: &str) -> Result<T, String> {
+        let response = self
+            .http
+            .get(format!("{}{}", self.base, path))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let body = read_body(response).await?;
+        return serde_json::from_value(body[field].clone()).map_err(|e| e.to_string());
+    }
+
+    async fn post<T: Serialize>(&self, path: &str, body: &T) -> Result<Value, String> {
+        let response = self
+            .http
+            .post(format!("{}{}", self.base, path))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        return read_body(response).await;
+    }
+
+    async fn address(&self, address: &str) -> Result<AddressEntity, String> {
+        return self
+            .get(&format!("/addresses/{}", address), "address")
+            .await;
+    }
+
+    async fn transactions(&self, address: &str) -> Result<Vec<TransactionEntity>, String> {
+        return self
+            .get(&format!("/addresses/{}/transactions", address), "txs")
+            .await;
+    }
+
+    /// The nonce the node expects next from `address`, see
+    /// `transaction_usecase::next_nonce`.
+    async fn next_nonce(&self, address: &str, transaction_ttl: i64) -> Result<u64, String> {
+        let account = self.address(address).await?;
+        let pending: Vec<TransactionEntity> = self
+            .transactions(address)
+            .await?
+            .into_iter()
+            .filter(|tx| tx.from == address && tx.status == TransactionStatus::Pending)
+            .collect();
+        return Ok(transaction_usecase::next_nonce(
+            account.nonce,
+            &pending,
+            Utc::now().timestamp(),
+            transaction_ttl,
+        ));
+    }
+}
+
+/// Returns the JSON body of a successful response, or the node's error.
+async fn read_body(response: reqwest::Response) -> Result<Value, String> {
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    let body: Value = serde_json::from_str(&text).unwrap_or(Value::String(text));
+    if !status.is_success() {
+        let reason = match body.get("error") {
+            Some(Value::String(error)) => error.clone(),
+            _ => body.to_string(),
+        };
+        return Err(format!("node returned {}: {}", status, reason));
+    }
+    return Ok(body);
+}
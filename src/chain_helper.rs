@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+
+use bson::oid::ObjectId;
+
+use crate::{
+    crypto_helper,
+    entities::{
+        block_entity::BlockEntity,
+        transaction_entity::{COINBASE_ADDRESS, TransactionEntity},
+    },
+    header_helper::{self, LEGACY_HEADER_VERSION},
+    models::block_model::{ChainReport, ChainViolation, InvalidBlock},
+    pow_helper,
+    setting::Mining,
+};
+
+/// Checks a chain ordered by index block by block and stops at the first
+/// block that breaks a rule. `transactions` holds the stored transactions
+/// the blocks list, by `_id`. Blocks up to `legacy_height` may be legacy
+/// blocks, see `check_block`.
+pub fn validate_chain(
+    blocks: &[BlockEntity],
+    transactions: &HashMap<ObjectId, TransactionEntity>,
+    legacy_height: u64,
+    mining: &Mining,
+) -> ChainReport {
+    for (i, block) in blocks.iter().enumerate() {
+        if let Some(violation) =
+            check_block(&blocks[..i], block, transactions, legacy_height, mining)
+        {
+            return invalid_chain(i as u64 + 1, block, violation);
+        }
+    }
+
+    return valid_chain(blocks.len() as u64);
+}
+
+pub fn valid_chain(checked_blocks: u64) -> ChainReport {
+    return ChainReport {
+        valid: true,
+        checked_blocks,
+        first_invalid: None,
+    };
+}
+
+/// The report of a chain whose `checked_blocks`th block, `block`, broke a
+/// rule.
+pub fn invalid_chain(
+    checked_blocks: u64,
+    block: &BlockEntity,
+    violation: ChainViolation,
+) -> ChainReport {
+    return ChainReport {
+        valid: false,
+        checked_blocks,
+        first_invalid: Some(InvalidBlock {
+            index: block.index,
+            hash: block.hash.clone(),
+            violation,
+        }),
+    };
+}
+
+/// Checks `block` against the blocks before it, ordered by index.
+/// `transactions` must hold the stored transactions `block` lists, by `_id`,
+/// it may hold others.
+///
+/// A legacy block, written by an older node and migrated, is accepted up
+/// to `legacy_height`, the last one the migration recorded. Its links and
+/// transactions are checked, its hash, work and coinbase can't be: they
+/// predate the current header and block reward.
+pub fn check_block(
+    previous_blocks: &[BlockEntity],
+    block: &BlockEntity,
+    transactions: &HashMap<ObjectId, TransactionEntity>,
+    legacy_height: u64,
+    mining: &Mining,
+) -> Option<ChainViolation> {
+    let previous = previous_blocks.last();
+
+    let expected_index = previous.map(|b| b.index + 1).unwrap_or(1);
+    if previous.is_some_and(|b| b.index == block.index) {
+        return Some(ChainViolation::DuplicateIndex);
+    }
+    if block.index != expected_index {
+        return Some(ChainViolation::IndexGap {
+            expected: expected_index,
+        });
+    }
+
+    let expected_previous_hash = previous.map(|b| b.hash.clone()).unwrap_or_default();
+    if block.previous_hash != expected_previous_hash {
+        return Some(ChainViolation::PreviousHashMismatch {
+            expected: expected_previous_hash,
+        });
+    }
+
+    if let Some(previous) = previous
+        && block.timestamp < previous.timestamp
+    {
+        return Some(ChainViolation::TimestampNotMonotonic {
+            previous: previous.timestamp,
+        });
+    }
+
+    let leaves = match transaction_leaves(&block.transaction_hashes) {
+        Some(leaves) if leaves.len() == block.transactions.len() => leaves,
+        _ => {
+            return Some(ChainViolation::MalformedHeader {
+                error: "transaction hashes do not match transactions".to_string(),
+            });
+        }
+    };
+    // a repeated leaf can extend an odd level without changing the root
+    let mut seen = HashSet::new();
+    if let Some(position) = leaves.iter().position(|leaf| !seen.insert(*leaf)) {
+        return Some(ChainViolation::DuplicateTransaction {
+            position: position as u64,
+        });
+    }
+    if let Some(violation) = check_transactions(block, transactions) {
+        return Some(violation);
+    }
+    let legacy = block.version == LEGACY_HEADER_VERSION && block.index <= legacy_height;
+    if !legacy && let Some(violation) = check_coinbase(block, transactions, mining) {
+        return Some(violation);
+    }
+    let expected_merkle_root = hex::encode(crypto_helper::merkle_root(&leaves));
+    if block.merkle_root != expected_merkle_root {
+        return Some(ChainViolation::MerkleRootMismatch {
+            expected: expected_merkle_root,
+        });
+    }
+    if legacy {
+        return None;
+    }
+
+    let expected_hash = match header_helper::calculate_hash(block) {
+        Ok(hash) => hash,
+        Err(e) => return Some(ChainViolation::MalformedHeader { error: e }),
+    };
+    if block.hash != expected_hash {
+        return Some(ChainViolation::HashMismatch {
+            expected: expected_hash,
+        });
+    }
+
+    let expected_difficulty = pow_helper::next_difficulty(previous_blocks, mining);
+    if block.difficulty != expected_difficulty {
+        return Some(ChainViolation::DifficultyMismatch {
+            expected: expected_difficulty,
+        });
+    }
+
+    if !pow_helper::meets_difficulty(&block.hash, block.difficulty) {
+        return Some(ChainViolation::InsufficientWork);
+    }
+
+    return None;
+}
+
+/// Recomputes the hash of every stored transaction the block lists and
+/// compares it with the one committed at the same position, so an edited
+/// transaction or a swapped `_id` no longer matches the merkle root.
+fn check_transactions(
+    block: &BlockEntity,
+    transactions: &HashMap<ObjectId, TransactionEntity>,
+) -> Option<ChainViolation> {
+    let committed = block.transactions.iter().zip(&block.transaction_hashes);
+    for (position, (tx_id, committed_hash)) in committed.enumerate() {
+        let tx = match transactions.get(tx_id) {
+            Some(tx) => tx,
+            None => {
+                return Some(ChainViolation::TransactionNotFound {
+                    position: position as u64,
+                    tx_id: tx_id.to_hex(),
+                });
+            }
+        };
+
+        let expected = hex::encode(crypto_helper::transaction_hash(tx));
+        if *committed_hash != expected {
+            return Some(ChainViolation::TransactionHashMismatch {
+                position: position as u64,
+                expected,
+            });
+        }
+    }
+
+    return None;
+}
+
+/// A block's first transaction, and only that one, is its coinbase. It
+/// issues `block_reward` and collects the fees of the other transactions.
+/// Runs after `check_transactions`, so every listed transaction is stored.
+fn check_coinbase(
+    block: &BlockEntity,
+    transactions: &HashMap<ObjectId, TransactionEntity>,
+    mining: &Mining,
+) -> Option<ChainViolation> {
+    let txs: Vec<&TransactionEntity> = block
+        .transactions
+        .iter()
+        .filter_map(|tx_id| transactions.get(tx_id))
+        .collect();
+    let (coinbase, others) = match txs.split_first() {
+        Some((coinbase, others)) if coinbase.from == COINBASE_ADDRESS => (coinbase, others),
+        _ => return Some(ChainViolation::MissingCoinbase),
+    };
+    if let Some(position) = others.iter().position(|tx| tx.from == COINBASE_ADDRESS) {
+        return Some(ChainViolation::ExtraCoinbase {
+            position: position as u64 + 1,
+        });
+    }
+
+    if coinbase.amount != mining.block_reward {
+        return Some(ChainViolation::CoinbaseRewardMismatch {
+            expected: mining.block_reward,
+        });
+    }
+    let fees = match others
+        .iter()
+        .try_fold(0u64, |sum, tx| sum.checked_add(tx.fee))
+    {
+        Some(fees) => fees,
+        None => return Some(ChainViolation::FeeOverflow),
+    };
+    if coinbase.fee != fees {
+        return Some(ChainViolation::CoinbaseFeeMismatch { expected: fees });
+    }
+
+    return None;
+}
+
+/// Decodes hex transaction hashes into merkle leaves.
+pub fn transaction_leaves(hashes: &[String]) -> Option<Vec<[u8; 32]>> {
+    return hashes
+        .iter()
+        .map(|hash| hex::decode(hash).ok()?.try_into().ok())
+        .collect();
+}
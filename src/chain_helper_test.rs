@@ -0,0 +1,396 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bson::oid::ObjectId;
+
+    use crate::{
+        chain_helper::validate_chain,
+        crypto_helper::{merkle_root, transaction_hash},
+        entities::{
+            block_entity::BlockEntity,
+            transaction_entity::{COINBASE_ADDRESS, TransactionEntity, TransactionStatus},
+        },
+        header_helper::LEGACY_HEADER_VERSION,
+        models::{
+            block_model::ChainViolation,
+            transaction_model::{SignatureScheme, TransactionPayload},
+        },
+        pow_helper,
+        setting::{Mining, Setting},
+        timer_helper::TimerHelper,
+    };
+
+    fn mining() -> Mining {
+        let setting = Setting::from_toml(include_str!("../Settings.toml")).unwrap();
+        return Mining {
+            difficulty: 4,
+            retarget_interval: 100,
+            target_block_time: 60,
+            max_adjustment_factor: 4,
+            ..setting.mining.clone()
+        };
+    }
+
+    type Transactions = HashMap<ObjectId, TransactionEntity>;
+
+    fn transaction(from: &str, amount: u64, fee: u64, nonce: u64) -> TransactionEntity {
+        let payload = TransactionPayload {
+            chain_id: 1,
+            from: String::from(from),
+            to: String::from("RLkZJhcqT2ac31oqBeLvo6egULQR54LNxb"),
+            amount,
+            fee,
+            nonce,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+        };
+        let mut tx = TransactionEntity::new(
+            &payload,
+            String::from("public_key"),
+            String::from("signature"),
+            TransactionStatus::Confirmed,
+            TimerHelper::Mock.creation(),
+        );
+        tx.id = Some(ObjectId::new());
+        return tx;
+    }
+
+    fn transfer(nonce: u64) -> TransactionEntity {
+        return transaction("RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh", 10, 1, nonce);
+    }
+
+    /// Mines `block` again over `txs`, `block.transactions` becomes their ids.
+    fn commit(block: &mut BlockEntity, txs: &[&TransactionEntity]) {
+        let leaves: Vec<[u8; 32]> = txs.iter().map(|tx| transaction_hash(tx)).collect();
+        block.transactions = txs.iter().map(|tx| tx.id.unwrap()).collect();
+        block.transaction_hashes = leaves.iter().map(hex::encode).collect();
+        block.merkle_root = hex::encode(merkle_root(&leaves));
+        pow_helper::mine(block).unwrap();
+    }
+
+    /// `count` mined blocks of a coinbase and one transfer each, and those
+    /// transactions.
+    fn chain(count: u64) -> (Vec<BlockEntity>, Transactions) {
+        let mut blocks: Vec<BlockEntity> = Vec::new();
+        let mut transactions = HashMap::new();
+        for index in 1..=count {
+            let previous_hash = blocks.last().map(|b| b.hash.clone()).unwrap_or_default();
+            let coinbase = transaction(COINBASE_ADDRESS, mining().block_reward, 1, index);
+            let tx = transfer(index);
+            let mut block = BlockEntity::new(
+                index,
+                vec![],
+                vec![],
+                previous_hash,
+                String::new(),
+                4,
+                TimerHelper::Mock.creation(),
+            );
+            commit(&mut block, &[&coinbase, &tx]);
+            blocks.push(block);
+            transactions.insert(coinbase.id.unwrap(), coinbase);
+            transactions.insert(tx.id.unwrap(), tx);
+        }
+        return (blocks, transactions);
+    }
+
+    /// `chain(3)` whose first two blocks were written by an older node, one
+    /// transfer each and no coinbase, hashed over an unknown header.
+    fn legacy_chain() -> (Vec<BlockEntity>, Transactions) {
+        let (mut blocks, mut transactions) = chain(3);
+        for (i, block) in blocks.iter_mut().take(2).enumerate() {
+            let tx = transfer(10 + i as u64);
+            let leaf = transaction_hash(&tx);
+            block.version = LEGACY_HEADER_VERSION;
+            block.difficulty = 0;
+            block.transactions = vec![tx.id.unwrap()];
+            block.transaction_hashes = vec![hex::encode(leaf)];
+            block.merkle_root = hex::encode(merkle_root(&[leaf]));
+            block.hash = hex::encode([i as u8 + 1; 32]);
+            transactions.insert(tx.id.unwrap(), tx);
+        }
+        blocks[1].previous_hash = blocks[0].hash.clone();
+        blocks[2].previous_hash = blocks[1].hash.clone();
+        pow_helper::mine(&mut blocks[2]).unwrap();
+        return (blocks, transactions);
+    }
+
+    #[test]
+    fn validate_chain_valid_test() {
+        let (blocks, transactions) = chain(3);
+        let report = validate_chain(&blocks, &transactions, 0, &mining());
+
+        assert!(report.valid);
+        assert_eq!(report.checked_blocks, 3);
+        assert_eq!(report.first_invalid, None);
+    }
+
+    #[test]
+    fn validate_chain_tampered_transactions_test() {
+        let (mut blocks, mut transactions) = chain(3);
+        // without a fee the coinbase still adds up
+        let tx = transaction("RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh", 10, 0, 9);
+        blocks[1].transactions.push(tx.id.unwrap());
+        blocks[1]
+            .transaction_hashes
+            .push(hex::encode(transaction_hash(&tx)));
+        transactions.insert(tx.id.unwrap(), tx);
+
+        let report = validate_chain(&blocks, &transactions, 0, &mining());
+        let invalid = report.first_invalid.unwrap();
+
+        assert!(!report.valid);
+        assert_eq!(invalid.index, 2);
+        assert!(matches!(
+            invalid.violation,
+            ChainViolation::MerkleRootMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn validate_chain_tampered_confirmed_transaction_test() {
+        let (blocks, mut transactions) = chain(3);
+        let tx = transactions.get_mut(&blocks[1].transactions[1]).unwrap();
+        tx.amount = 1_000;
+        let expected = hex::encode(transaction_hash(tx));
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+
+        assert_eq!(invalid.index, 2);
+        assert_eq!(
+            invalid.violation,
+            ChainViolation::TransactionHashMismatch {
+                position: 1,
+                expected,
+            }
+        );
+    }
+
+    #[test]
+    fn validate_chain_swapped_transaction_id_test() {
+        let (mut blocks, transactions) = chain(3);
+        let (first, second) = (blocks[0].transactions[1], blocks[1].transactions[1]);
+        blocks[0].transactions[1] = second;
+        blocks[1].transactions[1] = first;
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+
+        assert_eq!(invalid.index, 1);
+        assert_eq!(
+            invalid.violation,
+            ChainViolation::TransactionHashMismatch {
+                position: 1,
+                expected: hex::encode(transaction_hash(&transactions[&second])),
+            }
+        );
+    }
+
+    #[test]
+    fn validate_chain_missing_transaction_test() {
+        let (blocks, mut transactions) = chain(3);
+        let tx_id = blocks[2].transactions[1];
+        transactions.remove(&tx_id);
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+
+        assert_eq!(invalid.index, 3);
+        assert_eq!(
+            invalid.violation,
+            ChainViolation::TransactionNotFound {
+                position: 1,
+                tx_id: tx_id.to_hex(),
+            }
+        );
+    }
+
+    #[test]
+    fn validate_chain_tampered_header_test() {
+        let (mut blocks, transactions) = chain(3);
+        blocks[2].nonce += 1;
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+
+        assert_eq!(invalid.index, 3);
+        assert!(matches!(
+            invalid.violation,
+            ChainViolation::HashMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn validate_chain_index_gap_test() {
+        let (mut blocks, transactions) = chain(3);
+        blocks.remove(1);
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+
+        assert_eq!(invalid.index, 3);
+        assert_eq!(invalid.violation, ChainViolation::IndexGap { expected: 2 });
+    }
+
+    #[test]
+    fn validate_chain_duplicate_index_test() {
+        let (mut blocks, transactions) = chain(2);
+        blocks.insert(1, blocks[0].clone());
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+
+        assert_eq!(invalid.index, 1);
+        assert_eq!(invalid.violation, ChainViolation::DuplicateIndex);
+    }
+
+    #[test]
+    fn validate_chain_timestamp_not_monotonic_test() {
+        let (mut blocks, transactions) = chain(2);
+        blocks[0].timestamp = 10;
+        pow_helper::mine(&mut blocks[0]).unwrap();
+        blocks[1].previous_hash = blocks[0].hash.clone();
+        pow_helper::mine(&mut blocks[1]).unwrap();
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+
+        assert_eq!(invalid.index, 2);
+        assert_eq!(
+            invalid.violation,
+            ChainViolation::TimestampNotMonotonic { previous: 10 }
+        );
+    }
+
+    fn coinbase(amount: u64, fee: u64) -> TransactionEntity {
+        return transaction(COINBASE_ADDRESS, amount, fee, 2);
+    }
+
+    /// Commits the second block of a chain to `txs` instead and returns the
+    /// violation reported for it.
+    fn second_block_violation(txs: Vec<TransactionEntity>) -> ChainViolation {
+        let (mut blocks, mut transactions) = chain(2);
+        commit(
+            &mut blocks[1],
+            &txs.iter().collect::<Vec<&TransactionEntity>>(),
+        );
+        for tx in txs {
+            transactions.insert(tx.id.unwrap(), tx);
+        }
+
+        let invalid = validate_chain(&blocks, &transactions, 0, &mining())
+            .first_invalid
+            .unwrap();
+        assert_eq!(invalid.index, 2);
+        return invalid.violation;
+    }
+
+    #[test]
+    fn validate_chain_duplicate_transaction_test() {
+        // [coinbase, a, b, b] has the merkle root of [coinbase, a, b]
+        let (a, b) = (transfer(2), transfer(3));
+
+        assert_eq!(
+            second_block_violation(vec![coinbase(50, 3), a, b.clone(), b]),
+            ChainViolation::DuplicateTransaction { position: 3 }
+        );
+    }
+
+    #[test]
+    fn validate_chain_missing_coinbase_test() {
+        assert_eq!(
+            second_block_violation(vec![transfer(2)]),
+            ChainViolation::MissingCoinbase
+        );
+        assert_eq!(
+            second_block_violation(vec![transfer(2), coinbase(50, 1)]),
+            ChainViolation::MissingCoinbase
+        );
+    }
+
+    #[test]
+    fn validate_chain_extra_coinbase_test() {
+        assert_eq!(
+            second_block_violation(vec![
+                coinbase(50, 0),
+                transaction(COINBASE_ADDRESS, 50, 0, 3),
+            ]),
+            ChainViolation::ExtraCoinbase { position: 1 }
+        );
+    }
+
+    #[test]
+    fn validate_chain_coinbase_reward_test() {
+        // a re-mined coinbase issuing more than block_reward
+        assert_eq!(
+            second_block_violation(vec![coinbase(5_000, 1), transfer(2)]),
+            ChainViolation::CoinbaseRewardMismatch { expected: 50 }
+        );
+    }
+
+    #[test]
+    fn validate_chain_coinbase_fee_test() {
+        assert_eq!(
+            second_block_violation(vec![coinbase(50, 3), transfer(2)]),
+            ChainViolation::CoinbaseFeeMismatch { expected: 1 }
+        );
+    }
+
+    #[test]
+    fn validate_chain_fee_overflow_test() {
+        let from = "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh";
+
+        assert_eq!(
+            second_block_violation(vec![
+                coinbase(50, 0),
+                transaction(from, 1, u64::MAX, 2),
+                transaction(from, 1, 1, 3),
+            ]),
+            ChainViolation::FeeOverflow
+        );
+    }
+
+    #[test]
+    fn validate_chain_accepts_legacy_blocks_test() {
+        let (blocks, transactions) = legacy_chain();
+
+        let report = validate_chain(&blocks, &transactions, 2, &mining());
+        assert!(report.valid);
+        assert_eq!(report.checked_blocks, 3);
+    }
+
+    #[test]
+    fn validate_chain_legacy_block_above_height_test() {
+        let (blocks, transactions) = legacy_chain();
+
+        let invalid = validate_chain(&blocks, &transactions, 1, &mining())
+            .first_invalid
+            .unwrap();
+        assert_eq!(invalid.index, 2);
+        assert_eq!(invalid.violation, ChainViolation::MissingCoinbase);
+    }
+
+    #[test]
+    fn validate_chain_checks_legacy_links_test() {
+        let (mut blocks, transactions) = legacy_chain();
+        blocks[1].previous_hash = hex::encode([9u8; 32]);
+
+        let invalid = validate_chain(&blocks, &transactions, 2, &mining())
+            .first_invalid
+            .unwrap();
+        assert_eq!(invalid.index, 2);
+        assert!(matches!(
+            invalid.violation,
+            ChainViolation::PreviousHashMismatch { .. }
+        ));
+    }
+}
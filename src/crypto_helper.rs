@@ -1,8 +1,37 @@
+use std::sync::LazyLock;
+
+use ripemd::{Digest, Ripemd160};
 use secp256k1::ecdsa::Signature;
 use secp256k1::hashes::{Hash, sha256};
-use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use secp256k1::{All, Keypair, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey, schnorr};
+
+use crate::{
+    entities::transaction_entity::TransactionEntity,
+    models::transaction_model::{SignatureScheme, TransactionPayload},
+};
+
+/// Domain tag prefixed to every signed transaction payload.
+pub const TRANSACTION_DOMAIN: &str = "rust_chain/transaction/v1";
+
+/// Domain tag prefixed to the proof of possession signed when registering
+/// an address.
+pub const ADDRESS_DOMAIN: &str = "rust_chain/address/v1";
+
+/// Version byte leading the Base58Check payload of every address.
+pub const ADDRESS_VERSION: u8 = 0x3c;
+
+/// Byte prefixed to a transaction hash before it becomes a merkle leaf.
+pub const MERKLE_LEAF_PREFIX: u8 = 0x00;
 
-fn _from_hex_to_secret_key(hex_str: &str) -> Result<SecretKey, secp256k1::Error> {
+/// Byte prefixed to the two children of an inner merkle node.
+pub const MERKLE_NODE_PREFIX: u8 = 0x01;
+
+/// Context shared by every signing and verifying call. Building one
+/// precomputes tables, far slower than a verification, and it is safe to
+/// use from many threads at once.
+pub static SECP256K1: LazyLock<Secp256k1<All>> = LazyLock::new(Secp256k1::new);
+
+fn from_hex_to_secret_key(hex_str: &str) -> Result<SecretKey, secp256k1::Error> {
     let bytes = hex::decode(hex_str).map_err(|_| secp256k1::Error::InvalidSecretKey)?;
     return SecretKey::from_slice(&bytes);
 }
@@ -17,27 +46,324 @@ fn from_hex_to_sig(hex_str: &str) -> Result<Signature, secp256k1::Error> {
     return Signature::from_der(&bytes);
 }
 
+/// Whether `hex_str` is a compressed secp256k1 public key, the form every
+/// address uses.
+pub fn is_compressed_public_key(hex_str: &str) -> bool {
+    let Ok(bytes) = hex::decode(hex_str) else {
+        return false;
+    };
+    return bytes.len() == 33 && PublicKey::from_slice(&bytes).is_ok();
+}
+
+/// Returns the hex compressed public key of a hex secret key.
+pub fn public_key_from_secret_key(secret_key: &str) -> Result<String, secp256k1::Error> {
+    let secret_key = from_hex_to_secret_key(secret_key)?;
+    return Ok(PublicKey::from_secret_key(&SECP256K1, &secret_key).to_string());
+}
+
+/// Derives the address of a public key: Base58Check of `ADDRESS_VERSION`
+/// and the RIPEMD-160 of the SHA-256 of the compressed key, followed by the
+/// first four bytes of the double SHA-256 of both as checksum.
+pub fn address_from_public_key(public_key: &str) -> Result<String, secp256k1::Error> {
+    let public_key = from_hex_to_public_key(public_key)?;
+    let key_hash = Ripemd160::digest(sha256::Hash::hash(&public_key.serialize()).as_byte_array());
+
+    let mut bytes = vec![ADDRESS_VERSION];
+    bytes.extend_from_slice(&key_hash);
+    let checksum = address_checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+    return Ok(bs58::encode(bytes).into_string());
+}
+
+/// Decodes an address and returns its public key hash, failing on a bad
+/// encoding, version byte or checksum.
+pub fn parse_address(address: &str) -> Result<[u8; 20], String> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|e| e.to_string())?;
+    if bytes.len() != 25 {
+        return Err(format!("address is {} bytes, expected 25", bytes.len()));
+    }
+    if bytes[0] != ADDRESS_VERSION {
+        return Err(format!("unknown address version {}", bytes[0]));
+    }
+    if bytes[21..] != address_checksum(&bytes[..21]) {
+        return Err("address checksum mismatch".to_string());
+    }
+
+    let mut key_hash = [0u8; 20];
+    key_hash.copy_from_slice(&bytes[1..21]);
+    return Ok(key_hash);
+}
+
+pub fn is_valid_address(address: &str) -> bool {
+    return parse_address(address).is_ok();
+}
+
+fn address_checksum(bytes: &[u8]) -> [u8; 4] {
+    let digest = sha256::Hash::hash(sha256::Hash::hash(bytes).as_byte_array());
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&digest.as_byte_array()[..4]);
+    return checksum;
+}
+
+/// Canonical bytes of a payload: the domain tag, then each field in order.
+/// Strings are prefixed with their byte length as a big-endian u32,
+/// integers are big-endian, an optional field is a 0 or 1 byte followed by
+/// the value when present and the scheme is one byte, 0 for ECDSA and 1
+/// for Schnorr. Signing the scheme keeps a signature from being checked
+/// under another scheme than the one its signer chose.
+pub fn payload_bytes(payload: &TransactionPayload) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::new();
+    put_bytes(&mut buf, TRANSACTION_DOMAIN.as_bytes());
+    buf.extend_from_slice(&payload.chain_id.to_be_bytes());
+    put_bytes(&mut buf, payload.from.as_bytes());
+    put_bytes(&mut buf, payload.to.as_bytes());
+    buf.extend_from_slice(&payload.amount.to_be_bytes());
+    buf.extend_from_slice(&payload.fee.to_be_bytes());
+    buf.extend_from_slice(&payload.nonce.to_be_bytes());
+    put_optional_i64(&mut buf, payload.valid_until);
+    buf.push(scheme_tag(payload.scheme));
+    return buf;
+}
+
+/// Bytes a key owner signs to register `public_key`: the domain tag, the
+/// chain id and the key, encoded like `payload_bytes`.
+pub fn registration_bytes(chain_id: u64, public_key: &str) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::new();
+    put_bytes(&mut buf, ADDRESS_DOMAIN.as_bytes());
+    buf.extend_from_slice(&chain_id.to_be_bytes());
+    put_bytes(&mut buf, public_key.as_bytes());
+    return buf;
+}
+
+fn scheme_tag(scheme: SignatureScheme) -> u8 {
+    return match scheme {
+        SignatureScheme::Ecdsa => 0,
+        SignatureScheme::Schnorr => 1,
+    };
+}
+
+fn digest_message(bytes: &[u8]) -> Message {
+    let digest = sha256::Hash::hash(bytes);
+    return Message::from_digest(digest.to_byte_array());
+}
+
+fn sign_message(message: &Message, secret_key: &str) -> Result<String, secp256k1::Error> {
+    let secret_key = from_hex_to_secret_key(secret_key)?;
+    let signature = SECP256K1.sign_ecdsa(message, &secret_key);
+    return Ok(hex::encode(signature.serialize_der()));
+}
+
+/// Signs the BIP340 way over the 32 byte message, returning 64 hex bytes.
+fn sign_schnorr_message(message: &Message, secret_key: &str) -> Result<String, secp256k1::Error> {
+    let keypair = Keypair::from_secret_key(&SECP256K1, &from_hex_to_secret_key(secret_key)?);
+    let signature = SECP256K1.sign_schnorr(message.as_ref(), &keypair);
+    return Ok(hex::encode(signature.to_byte_array()));
+}
+
+/// Signs a payload under its scheme and returns the hex signature expected
+/// by `verify_signature`.
+pub fn sign_payload(
+    payload: &TransactionPayload,
+    secret_key: &str,
+) -> Result<String, secp256k1::Error> {
+    let message = digest_message(&payload_bytes(payload));
+    return match payload.scheme {
+        SignatureScheme::Ecdsa => sign_message(&message, secret_key),
+        SignatureScheme::Schnorr => sign_schnorr_message(&message, secret_key),
+    };
+}
+
+/// Signs the proof of possession checked by `verify_registration`.
+pub fn sign_registration(
+    chain_id: u64,
+    public_key: &str,
+    secret_key: &str,
+) -> Result<String, secp256k1::Error> {
+    return sign_message(
+        &digest_message(&registration_bytes(chain_id, public_key)),
+        secret_key,
+    );
+}
+
+/// Checks `signature` over a payload under the scheme the payload declares.
 pub fn verify_signature(
-    message: &str,
+    payload: &TransactionPayload,
+    public_key: &str,
+    signature: &str,
+) -> Result<bool, secp256k1::Error> {
+    let message = digest_message(&payload_bytes(payload));
+    return match payload.scheme {
+        SignatureScheme::Ecdsa => verify_message(&message, public_key, signature),
+        SignatureScheme::Schnorr => verify_schnorr_message(&message, public_key, signature),
+    };
+}
+
+/// Checks that the holder of `public_key` signed its registration.
+pub fn verify_registration(
+    chain_id: u64,
     public_key: &str,
     signature: &str,
 ) -> Result<bool, secp256k1::Error> {
-    let digest = sha256::Hash::hash(message.as_bytes());
-    let message_from_digest = Message::from_digest(digest.to_byte_array());
+    return verify_message(
+        &digest_message(&registration_bytes(chain_id, public_key)),
+        public_key,
+        signature,
+    );
+}
 
-    let secp = Secp256k1::new();
-    let public_key_bytes = match from_hex_to_public_key(&public_key) {
+fn verify_message(
+    message: &Message,
+    public_key: &str,
+    signature: &str,
+) -> Result<bool, secp256k1::Error> {
+    let public_key_bytes = match from_hex_to_public_key(public_key) {
         Ok(r) => r,
         Err(e) => return Err(e),
     };
-    let signature_bytes = match from_hex_to_sig(&signature) {
+    let signature_bytes = match from_hex_to_sig(signature) {
         Ok(r) => r,
         Err(e) => return Err(e),
     };
 
-    let result = secp
-        .verify_ecdsa(&message_from_digest, &signature_bytes, &public_key_bytes)
+    let result = SECP256K1
+        .verify_ecdsa(message, &signature_bytes, &public_key_bytes)
         .is_ok();
 
     return Ok(result);
 }
+
+/// Verifies a BIP340 signature against the x-only part of a compressed
+/// key, so Schnorr and ECDSA senders share the same addresses.
+fn verify_schnorr_message(
+    message: &Message,
+    public_key: &str,
+    signature: &str,
+) -> Result<bool, secp256k1::Error> {
+    let public_key = XOnlyPublicKey::from(from_hex_to_public_key(public_key)?);
+    let signature_bytes = hex::decode(signature).map_err(|_| secp256k1::Error::InvalidSignature)?;
+    let signature = schnorr::Signature::from_slice(&signature_bytes)?;
+
+    let result = SECP256K1
+        .verify_schnorr(&signature, message.as_ref(), &public_key)
+        .is_ok();
+    return Ok(result);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+fn put_optional_i64(buf: &mut Vec<u8>, value: Option<i64>) {
+    match value {
+        Some(value) => {
+            buf.push(1);
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+        None => buf.push(0),
+    }
+}
+
+/// Canonical transaction bytes. Strings are prefixed with their byte length
+/// as a big-endian u32, integers are big-endian.
+pub fn transaction_bytes(tx: &TransactionEntity) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::new();
+    put_bytes(&mut buf, tx.from.as_bytes());
+    put_bytes(&mut buf, tx.to.as_bytes());
+    buf.extend_from_slice(&tx.amount.to_be_bytes());
+    buf.extend_from_slice(&tx.fee.to_be_bytes());
+    buf.extend_from_slice(&tx.nonce.to_be_bytes());
+    put_optional_i64(&mut buf, tx.valid_until);
+    buf.push(scheme_tag(tx.scheme));
+    put_bytes(&mut buf, tx.public_key.as_bytes());
+    put_bytes(&mut buf, tx.signature.as_bytes());
+    buf.extend_from_slice(&tx.timestamp.to_be_bytes());
+    return buf;
+}
+
+/// Canonical transaction hash used as a merkle leaf.
+pub fn transaction_hash(tx: &TransactionEntity) -> [u8; 32] {
+    return sha256::Hash::hash(&transaction_bytes(tx)).to_byte_array();
+}
+
+/// Computes a SHA-256 merkle root, an odd node on a level is paired with
+/// itself. Leaves and inner nodes are hashed under different prefixes, so
+/// an inner node can't pass for a transaction. An empty tree has an all
+/// zero root.
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        return [0u8; 32];
+    }
+
+    let mut level: Vec<[u8; 32]> = leaves.iter().map(hash_leaf).collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+    }
+    return level[0];
+}
+
+fn hash_leaf(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = [0u8; 33];
+    bytes[0] = MERKLE_LEAF_PREFIX;
+    bytes[1..].copy_from_slice(leaf);
+    return sha256::Hash::hash(&bytes).to_byte_array();
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = [0u8; 65];
+    bytes[0] = MERKLE_NODE_PREFIX;
+    bytes[1..33].copy_from_slice(left);
+    bytes[33..].copy_from_slice(right);
+    return sha256::Hash::hash(&bytes).to_byte_array();
+}
+
+/// Returns the sibling hashes from the leaf at `index` up to the root.
+pub fn merkle_branch(leaves: &[[u8; 32]], index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= leaves.len() {
+        return None;
+    }
+
+    let mut branch = Vec::new();
+    let mut level: Vec<[u8; 32]> = leaves.iter().map(hash_leaf).collect();
+    let mut index = index;
+    while level.len() > 1 {
+        let sibling = if index.is_multiple_of(2) {
+            level.get(index + 1).unwrap_or(&level[index])
+        } else {
+            &level[index - 1]
+        };
+        branch.push(*sibling);
+
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+        index /= 2;
+    }
+    return Some(branch);
+}
+
+/// Checks that `leaf` sits at `index` in the tree committed to by `root`.
+pub fn verify_merkle_proof(
+    leaf: &[u8; 32],
+    index: usize,
+    branch: &[[u8; 32]],
+    root: &[u8; 32],
+) -> bool {
+    let mut node = hash_leaf(leaf);
+    let mut index = index;
+    for sibling in branch {
+        node = if index.is_multiple_of(2) {
+            hash_pair(&node, sibling)
+        } else {
+            hash_pair(sibling, &node)
+        };
+        index /= 2;
+    }
+    return index == 0 && node == *root;
+}
@@ -0,0 +1,170 @@
+#[cfg(test)]
+mod tests {
+    use secp256k1::rand::rngs::OsRng;
+
+    use crate::{
+        crypto_helper::{
+            SECP256K1, address_from_public_key, is_valid_address, merkle_branch, merkle_root,
+            payload_bytes, public_key_from_secret_key, sign_payload, transaction_hash,
+            verify_merkle_proof, verify_signature,
+        },
+        entities::transaction_entity::{TransactionEntity, TransactionStatus},
+        models::transaction_model::{SignatureScheme, TransactionPayload},
+        timer_helper::TimerHelper,
+    };
+
+    fn payload(from: &str, to: &str, amount: u64) -> TransactionPayload {
+        return TransactionPayload {
+            chain_id: 1,
+            from: String::from(from),
+            to: String::from(to),
+            amount,
+            fee: 0,
+            nonce: 0,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+        };
+    }
+
+    fn leaves(count: u8) -> Vec<[u8; 32]> {
+        return (0..count).map(|i| [i; 32]).collect();
+    }
+
+    #[test]
+    fn merkle_proof_round_trip_test() {
+        for count in 1..=7 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves);
+
+            for (index, leaf) in leaves.iter().enumerate() {
+                let branch = merkle_branch(&leaves, index).unwrap();
+                assert!(verify_merkle_proof(leaf, index, &branch, &root));
+            }
+        }
+    }
+
+    #[test]
+    fn merkle_proof_rejects_wrong_leaf_test() {
+        let leaves = leaves(5);
+        let root = merkle_root(&leaves);
+        let branch = merkle_branch(&leaves, 2).unwrap();
+
+        assert!(!verify_merkle_proof(&leaves[3], 2, &branch, &root));
+        assert!(!verify_merkle_proof(&leaves[2], 3, &branch, &root));
+        assert!(merkle_branch(&leaves, 5).is_none());
+    }
+
+    #[test]
+    fn merkle_proof_rejects_inner_node_test() {
+        let leaves = leaves(4);
+        let root = merkle_root(&leaves);
+        let branch = merkle_branch(&leaves, 0).unwrap();
+        // the parent of the first two leaves, proven one level up
+        let inner = merkle_root(&leaves[..2]);
+
+        assert!(!verify_merkle_proof(&inner, 0, &branch[1..], &root));
+    }
+
+    #[test]
+    fn transaction_hash_covers_fields_test() {
+        let tx = TransactionEntity::new(
+            &payload("ab", "cd", 12),
+            String::from("key"),
+            String::from("sig"),
+            TransactionStatus::Pending,
+            TimerHelper::Mock.creation(),
+        );
+        let mut other = tx.clone();
+        other.nonce = 1;
+
+        assert_eq!(tx.hash, hex::encode(transaction_hash(&tx)));
+        assert_ne!(transaction_hash(&tx), transaction_hash(&other));
+    }
+
+    #[test]
+    fn payload_bytes_are_unambiguous_test() {
+        assert_ne!(
+            payload_bytes(&payload("a", "ab", 12)),
+            payload_bytes(&payload("a", "ab1", 2))
+        );
+    }
+
+    #[test]
+    fn sign_and_verify_payload_test() {
+        let (secret_key, public_key) = SECP256K1.generate_keypair(&mut OsRng);
+        let public_key = public_key.to_string();
+        let payload = payload(&public_key, "receiver", 10);
+
+        let signature = sign_payload(&payload, &hex::encode(secret_key.secret_bytes())).unwrap();
+        assert!(verify_signature(&payload, &public_key, &signature).unwrap());
+
+        let mut other_chain = payload.clone();
+        other_chain.chain_id = 2;
+        assert!(!verify_signature(&other_chain, &public_key, &signature).unwrap());
+    }
+
+    #[test]
+    fn sign_and_verify_schnorr_payload_test() {
+        // 6G has an odd y, the x-only key must still verify
+        for n in 1u8..=8 {
+            let mut secret_key = [0u8; 32];
+            secret_key[31] = n;
+            let secret_key = hex::encode(secret_key);
+            let public_key = public_key_from_secret_key(&secret_key).unwrap();
+            let mut payload = payload(&public_key, "receiver", 10);
+            payload.scheme = SignatureScheme::Schnorr;
+
+            let signature = sign_payload(&payload, &secret_key).unwrap();
+            assert_eq!(signature.len(), 128);
+            assert!(verify_signature(&payload, &public_key, &signature).unwrap());
+
+            let mut other_amount = payload.clone();
+            other_amount.amount = 11;
+            assert!(!verify_signature(&other_amount, &public_key, &signature).unwrap());
+        }
+    }
+
+    #[test]
+    fn signature_scheme_is_signed_test() {
+        let (secret_key, public_key) = SECP256K1.generate_keypair(&mut OsRng);
+        let secret_key = hex::encode(secret_key.secret_bytes());
+        let public_key = public_key.to_string();
+        let ecdsa = payload(&public_key, "receiver", 10);
+        let mut schnorr = ecdsa.clone();
+        schnorr.scheme = SignatureScheme::Schnorr;
+
+        assert_ne!(payload_bytes(&ecdsa), payload_bytes(&schnorr));
+
+        let signature = sign_payload(&schnorr, &secret_key).unwrap();
+        assert!(!matches!(
+            verify_signature(&ecdsa, &public_key, &signature),
+            Ok(true)
+        ));
+        let signature = sign_payload(&ecdsa, &secret_key).unwrap();
+        assert!(!matches!(
+            verify_signature(&schnorr, &public_key, &signature),
+            Ok(true)
+        ));
+    }
+
+    #[test]
+    fn address_vector_test() {
+        // the generator point, its RIPEMD-160 of SHA-256 is 751e76e8...3bd6
+        let address = address_from_public_key(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+
+        assert_eq!(address, "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh");
+        assert!(is_valid_address(&address));
+    }
+
+    #[test]
+    fn address_rejects_typo_test() {
+        assert!(!is_valid_address("RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLj"));
+        assert!(!is_valid_address("RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnL"));
+        assert!(!is_valid_address(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        ));
+    }
+}
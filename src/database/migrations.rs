@@ -0,0 +1,391 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use bson::{Bson, Document, doc, from_document, oid::ObjectId};
+use mongodb::Database;
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::{
+    chain_helper, crypto_helper, entities::transaction_entity::TransactionEntity,
+    header_helper::LEGACY_HEADER_VERSION, timer_helper::IntoTimerHelperShared,
+};
+
+/// Collection recording the applied migrations, one document per version.
+pub const MIGRATIONS_COLLECTION: &str = "_migrations";
+
+/// Rewrites the documents of one collection that an older node wrote, so
+/// the current entities can read them.
+#[async_trait]
+pub trait Migration: Send + Sync {
+    /// Migrations run in increasing version order, a version is never
+    /// reused.
+    fn version(&self) -> u32;
+    fn name(&self) -> &'static str;
+    fn collection(&self) -> &'static str;
+
+    /// The documents to rewrite. A rewritten document must no longer
+    /// match, so a migration that stopped midway can simply run again.
+    fn filter(&self) -> Document;
+
+    /// The fields to `$set` on `document`.
+    async fn update(&self, db: &Database, document: &Document) -> Result<Document, String>;
+
+    /// Extra fields stored in the `_migrations` record once every document
+    /// was rewritten.
+    async fn record(&self, _db: &Database) -> Result<Document, String> {
+        return Ok(doc! {});
+    }
+}
+
+/// Every migration, in version order.
+pub fn migrations() -> Vec<Box<dyn Migration>> {
+    return vec![
+        Box::new(AddressFromPublicKey),
+        Box::new(TransactionFields),
+        Box::new(BlockHeaderFields),
+    ];
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct MigrationReport {
+    pub version: u32,
+    pub name: &'static str,
+    /// Documents rewritten, or with a dry run the documents matching the
+    /// filter now.
+    pub documents: u64,
+}
+
+/// Migrations not recorded in `_migrations` yet, in version order.
+pub async fn pending(db: &Database) -> Result<Vec<Box<dyn Migration>>, String> {
+    let applied: HashSet<i64> = db
+        .collection::<Document>(MIGRATIONS_COLLECTION)
+        .distinct("_id", doc! {})
+        .await
+        .map_err(|e| {
+            error!("read applied migrations failed: {}", e);
+            return e.to_string();
+        })?
+        .iter()
+        .filter_map(Bson::as_i64)
+        .collect();
+
+    return Ok(migrations()
+        .into_iter()
+        .filter(|m| !applied.contains(&(m.version() as i64)))
+        .collect());
+}
+
+/// Applies the pending migrations in order and records each one. A dry run
+/// only counts the documents each one would rewrite, migrations that
+/// depend on an earlier one may then report fewer than they will rewrite.
+pub async fn migrate(
+    db: &Database,
+    dry_run: bool,
+    t: IntoTimerHelperShared,
+) -> Result<Vec<MigrationReport>, String> {
+    let mut reports = Vec::new();
+    for migration in pending(db).await? {
+        let documents = match dry_run {
+            true => count(db, migration.as_ref()).await?,
+            false => apply(db, migration.as_ref()).await?,
+        };
+        if !dry_run {
+            let mut record = doc! {
+                "_id": migration.version() as i64,
+                "name": migration.name(),
+                "documents": documents as i64,
+                "applied_at": t.now(),
+            };
+            record.extend(migration.record(db).await?);
+            db.collection::<Document>(MIGRATIONS_COLLECTION)
+                .insert_one(record)
+                .await
+                .map_err(|e| {
+                    error!("record migration {} failed: {}", migration.version(), e);
+                    return e.to_string();
+                })?;
+            info!(
+                "migration {} {} rewrote {} documents",
+                migration.version(),
+                migration.name(),
+                documents
+            );
+        }
+
+        reports.push(MigrationReport {
+            version: migration.version(),
+            name: migration.name(),
+            documents,
+        });
+    }
+
+    return Ok(reports);
+}
+
+async fn count(db: &Database, migration: &dyn Migration) -> Result<u64, String> {
+    return db
+        .collection::<Document>(migration.collection())
+        .count_documents(migration.filter())
+        .await
+        .map_err(|e| {
+            error!(
+                "count documents of migration {} failed: {}",
+                migration.version(),
+                e
+            );
+            return e.to_string();
+        });
+}
+
+async fn apply(db: &Database, migration: &dyn Migration) -> Result<u64, String> {
+    let collection = db.collection::<Document>(migration.collection());
+    let mut cursor = collection
+        .find(migration.filter())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut documents = 0;
+    while cursor.advance().await.map_err(|e| e.to_string())? {
+        let document = cursor.deserialize_current().map_err(|e| e.to_string())?;
+        let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+        let update = migration.update(db, &document).await.map_err(|e| {
+            let message = format!(
+                "migration {} cannot rewrite {}: {}",
+                migration.version(),
+                id,
+                e
+            );
+            error!("{}", message);
+            return message;
+        })?;
+
+        collection
+            .update_one(doc! { "_id": id }, doc! { "$set": update })
+            .await
+            .map_err(|e| e.to_string())?;
+        documents += 1;
+    }
+
+    return Ok(documents);
+}
+
+/// `value` when it is a public key, its address. Addresses and `coinbase`
+/// are kept.
+fn to_address(value: &str) -> String {
+    if !crypto_helper::is_compressed_public_key(value) {
+        return value.to_string();
+    }
+    return crypto_helper::address_from_public_key(value).unwrap_or_else(|_| value.to_string());
+}
+
+fn get_str<'a>(document: &'a Document, field: &str) -> Result<&'a str, String> {
+    return document
+        .get_str(field)
+        .map_err(|e| format!("{}: {}", field, e));
+}
+
+/// Addresses used to be their public key. Derives the hashed address and
+/// starts the nonce at zero.
+pub struct AddressFromPublicKey;
+
+#[async_trait]
+impl Migration for AddressFromPublicKey {
+    fn version(&self) -> u32 {
+        return 1;
+    }
+
+    fn name(&self) -> &'static str {
+        return "address_from_public_key";
+    }
+
+    fn collection(&self) -> &'static str {
+        return "addresses";
+    }
+
+    fn filter(&self) -> Document {
+        return doc! { "address": { "$exists": false } };
+    }
+
+    async fn update(&self, _db: &Database, document: &Document) -> Result<Document, String> {
+        return address_update(document);
+    }
+}
+
+pub fn address_update(document: &Document) -> Result<Document, String> {
+    let public_key = get_str(document, "public_key")?;
+    let address = crypto_helper::address_from_public_key(public_key).map_err(|e| e.to_string())?;
+
+    let mut update = doc! { "address": address };
+    if !document.contains_key("nonce") {
+        update.insert("nonce", 0_i64);
+    }
+    return Ok(update);
+}
+
+/// Transactions used to name public keys in `from` and `to` and had no
+/// fee, nonce nor hash. Moves them to addresses, keeps the sender key in
+/// `public_key` and stores the hash of the result.
+pub struct TransactionFields;
+
+#[async_trait]
+impl Migration for TransactionFields {
+    fn version(&self) -> u32 {
+        return 2;
+    }
+
+    fn name(&self) -> &'static str {
+        return "transaction_fields";
+    }
+
+    fn collection(&self) -> &'static str {
+        return "transactions";
+    }
+
+    fn filter(&self) -> Document {
+        return doc! { "hash": { "$exists": false } };
+    }
+
+    async fn update(&self, _db: &Database, document: &Document) -> Result<Document, String> {
+        return transaction_update(document);
+    }
+}
+
+pub fn transaction_update(document: &Document) -> Result<Document, String> {
+    let from = get_str(document, "from")?;
+    let to = get_str(document, "to")?;
+
+    let mut update = doc! {
+        "from": to_address(from),
+        "to": to_address(to),
+    };
+    if crypto_helper::is_compressed_public_key(from) && !document.contains_key("public_key") {
+        update.insert("public_key", from);
+    }
+    for field in ["fee", "nonce"] {
+        if !document.contains_key(field) {
+            update.insert(field, 0_i64);
+        }
+    }
+
+    let mut migrated = document.clone();
+    migrated.extend(update.clone());
+    migrated.insert("hash", "");
+    let tx: TransactionEntity = from_document(migrated).map_err(|e| e.to_string())?;
+    update.insert("hash", hex::encode(crypto_helper::transaction_hash(&tx)));
+    return Ok(update);
+}
+
+/// Blocks used to have no version, difficulty nor merkle root. Commits
+/// them to the hashes of their transactions, so the transactions must be
+/// migrated first. Their stored hash was computed over the old header, so
+/// they get `LEGACY_HEADER_VERSION` and the record keeps the last one in
+/// `legacy_height`, up to which `GET /blocks/verify` accepts them.
+pub struct BlockHeaderFields;
+
+/// The index of the last legacy block `BlockHeaderFields` recorded, 0 when
+/// it has not run or found none.
+pub async fn legacy_height(db: &Database) -> Result<u64, String> {
+    let record = db
+        .collection::<Document>(MIGRATIONS_COLLECTION)
+        .find_one(doc! { "_id": BlockHeaderFields.version() as i64 })
+        .await
+        .map_err(|e| {
+            error!("read the legacy height failed: {}", e);
+            return e.to_string();
+        })?;
+    return Ok(match record.and_then(|r| r.get_i64("legacy_height").ok()) {
+        Some(height) => height as u64,
+        None => 0,
+    });
+}
+
+#[async_trait]
+impl Migration for BlockHeaderFields {
+    fn version(&self) -> u32 {
+        return 3;
+    }
+
+    fn name(&self) -> &'static str {
+        return "block_header_fields";
+    }
+
+    fn collection(&self) -> &'static str {
+        return "blocks";
+    }
+
+    fn filter(&self) -> Document {
+        return doc! { "merkle_root": { "$exists": false } };
+    }
+
+    async fn update(&self, db: &Database, document: &Document) -> Result<Document, String> {
+        let ids: Vec<ObjectId> = document
+            .get_array("transactions")
+            .map_err(|e| format!("transactions: {}", e))?
+            .iter()
+            .filter_map(Bson::as_object_id)
+            .collect();
+
+        let mut cursor = db
+            .collection::<Document>("transactions")
+            .find(doc! { "_id": { "$in": ids.clone() } })
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut hashes = HashMap::new();
+        while cursor.advance().await.map_err(|e| e.to_string())? {
+            let tx = cursor.deserialize_current().map_err(|e| e.to_string())?;
+            if let (Ok(id), Ok(hash)) = (tx.get_object_id("_id"), tx.get_str("hash")) {
+                hashes.insert(id, hash.to_string());
+            }
+        }
+
+        let mut ordered = Vec::with_capacity(ids.len());
+        for id in ids {
+            match hashes.remove(&id) {
+                Some(hash) => ordered.push(hash),
+                None => return Err(format!("transaction {} has no hash", id)),
+            }
+        }
+        return block_update(document, ordered);
+    }
+
+    async fn record(&self, db: &Database) -> Result<Document, String> {
+        let last = db
+            .collection::<Document>("blocks")
+            .find_one(doc! { "version": LEGACY_HEADER_VERSION as i64 })
+            .sort(doc! { "index": -1 })
+            .await
+            .map_err(|e| e.to_string())?;
+        let height = match last {
+            Some(block) => block
+                .get_i64("index")
+                .map_err(|e| format!("index: {}", e))?,
+            None => 0,
+        };
+        return Ok(doc! { "legacy_height": height });
+    }
+}
+
+/// The header fields of `document` whose transactions hash to
+/// `transaction_hashes`, in block order.
+pub fn block_update(
+    document: &Document,
+    transaction_hashes: Vec<String>,
+) -> Result<Document, String> {
+    let leaves = match chain_helper::transaction_leaves(&transaction_hashes) {
+        Some(leaves) => leaves,
+        None => return Err("malformed transaction hash".to_string()),
+    };
+
+    let mut update = doc! {
+        "merkle_root": hex::encode(crypto_helper::merkle_root(&leaves)),
+        "transaction_hashes": transaction_hashes,
+    };
+    if !document.contains_key("version") {
+        update.insert("version", LEGACY_HEADER_VERSION as i64);
+    }
+    if !document.contains_key("difficulty") {
+        update.insert("difficulty", 0_i64);
+    }
+    return Ok(update);
+}
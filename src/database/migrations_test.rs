@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+    use bson::{doc, from_document, oid::ObjectId};
+
+    use crate::{
+        crypto_helper::{merkle_root, transaction_hash},
+        database::migrations::{address_update, block_update, migrations, transaction_update},
+        entities::{
+            address_entity::AddressEntity, block_entity::BlockEntity,
+            transaction_entity::TransactionEntity,
+        },
+        header_helper::LEGACY_HEADER_VERSION,
+    };
+
+    // the public keys of secret keys 1 and 3, and their addresses
+    const SENDER_KEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const SENDER: &str = "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh";
+    const RECEIVER_KEY: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+    const RECEIVER: &str = "RLkZJhcqT2ac31oqBeLvo6egULQR54LNxb";
+
+    #[test]
+    fn migration_versions_increase_test() {
+        let versions: Vec<u32> = migrations().iter().map(|m| m.version()).collect();
+
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(versions[0], 1);
+    }
+
+    #[test]
+    fn address_update_test() {
+        let mut document = doc! {
+            "_id": ObjectId::new(),
+            "public_key": SENDER_KEY,
+            "balance": 5_i64,
+            "created_at": 0_i64,
+            "updated_at": 0_i64,
+        };
+
+        let update = address_update(&document).unwrap();
+        assert_eq!(update, doc! { "address": SENDER, "nonce": 0_i64 });
+
+        document.extend(update);
+        let address: AddressEntity = from_document(document).unwrap();
+        assert_eq!(address.public_key.as_deref(), Some(SENDER_KEY));
+        assert_eq!(address.balance, 5);
+    }
+
+    #[test]
+    fn transaction_update_test() {
+        let mut document = doc! {
+            "_id": ObjectId::new(),
+            "block_hash": null,
+            "from": SENDER_KEY,
+            "to": RECEIVER_KEY,
+            "amount": 3_i64,
+            "signature": "signature",
+            "timestamp": 10_i64,
+            "status": "confirmed",
+        };
+
+        document.extend(transaction_update(&document).unwrap());
+        let tx: TransactionEntity = from_document(document).unwrap();
+
+        assert_eq!(tx.from, SENDER);
+        assert_eq!(tx.to, RECEIVER);
+        assert_eq!(tx.public_key, SENDER_KEY);
+        assert_eq!((tx.amount, tx.fee, tx.nonce), (3, 0, 0));
+        assert_eq!(tx.hash, hex::encode(transaction_hash(&tx)));
+    }
+
+    #[test]
+    fn block_update_test() {
+        let hashes = vec![hex::encode([1u8; 32]), hex::encode([2u8; 32])];
+        let mut document = doc! {
+            "_id": ObjectId::new(),
+            "index": 1_i64,
+            "timestamp": 0_i64,
+            "transactions": [ObjectId::new(), ObjectId::new()],
+            "previous_hash": "",
+            "hash": "00",
+            "nonce": 7_i64,
+        };
+
+        document.extend(block_update(&document, hashes.clone()).unwrap());
+        let block: BlockEntity = from_document(document).unwrap();
+
+        assert_eq!(block.transaction_hashes, hashes);
+        assert_eq!(
+            block.merkle_root,
+            hex::encode(merkle_root(&[[1; 32], [2; 32]]))
+        );
+        assert_eq!(
+            (block.version, block.difficulty, block.nonce),
+            (LEGACY_HEADER_VERSION, 0, 7)
+        );
+        assert!(block_update(&doc! {}, vec![String::from("zz")]).is_err());
+    }
+}
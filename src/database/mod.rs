@@ -1 +1,4 @@
-pub mod database;
\ No newline at end of file
+pub mod database;
+pub mod schema;
+pub mod migrations;
+pub mod migrations_test;
@@ -0,0 +1,193 @@
+use bson::{Document, doc};
+use mongodb::{Database, IndexModel, options::IndexOptions};
+use tracing::{error, info};
+
+use crate::setting::Setting;
+
+const INTEGER: [&str; 2] = ["long", "int"];
+
+fn index(keys: Document, name: &str) -> IndexModel {
+    return IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().name(name.to_string()).build())
+        .build();
+}
+
+fn unique_index(keys: Document, name: &str, partial: Option<Document>) -> IndexModel {
+    return IndexModel::builder()
+        .keys(keys)
+        .options(
+            IndexOptions::builder()
+                .name(name.to_string())
+                .unique(true)
+                .partial_filter_expression(partial)
+                .build(),
+        )
+        .build();
+}
+
+/// Indexes of every collection. The unique ones are also enforced by the
+/// memory and redb backends.
+pub fn indexes() -> Vec<(&'static str, Vec<IndexModel>)> {
+    return vec![
+        (
+            "addresses",
+            vec![
+                unique_index(doc! { "address": 1 }, "address_unique", None),
+                // receivers registered by a transfer have no public key yet
+                unique_index(
+                    doc! { "public_key": 1 },
+                    "public_key_unique",
+                    Some(doc! { "public_key": { "$type": "string" } }),
+                ),
+            ],
+        ),
+        (
+            "transactions",
+            vec![
+                // find_all_pending and the expiry sweep
+                index(doc! { "status": 1, "timestamp": 1 }, "status_timestamp"),
+                // find_pending_by_sender and the sent side of find_by_address
+                index(
+                    doc! { "from": 1, "status": 1, "nonce": 1 },
+                    "from_status_nonce",
+                ),
+                index(doc! { "to": 1, "timestamp": 1 }, "to_timestamp"),
+                index(
+                    doc! { "block_hash": 1, "timestamp": 1 },
+                    "block_hash_timestamp",
+                ),
+            ],
+        ),
+        (
+            "blocks",
+            vec![
+                unique_index(doc! { "hash": 1 }, "hash_unique", None),
+                unique_index(doc! { "index": 1 }, "index_unique", None),
+            ],
+        ),
+    ];
+}
+
+/// `$jsonSchema` validators matching the entities, so a document
+/// `from_document` cannot read is refused on write.
+pub fn validators() -> Vec<(&'static str, Document)> {
+    return vec![
+        (
+            "addresses",
+            doc! {
+                "bsonType": "object",
+                "required": ["address", "balance", "nonce", "created_at", "updated_at"],
+                "properties": {
+                    "address": { "bsonType": "string" },
+                    "public_key": { "bsonType": ["string", "null"] },
+                    "balance": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "nonce": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "created_at": { "bsonType": INTEGER.to_vec() },
+                    "updated_at": { "bsonType": INTEGER.to_vec() },
+                },
+            },
+        ),
+        (
+            "transactions",
+            doc! {
+                "bsonType": "object",
+                "required": [
+                    "hash", "from", "to", "amount", "fee", "nonce", "signature", "timestamp",
+                    "status",
+                ],
+                "properties": {
+                    "hash": { "bsonType": "string" },
+                    "block_hash": { "bsonType": ["string", "null"] },
+                    "from": { "bsonType": "string" },
+                    "to": { "bsonType": "string" },
+                    "amount": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "fee": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "nonce": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "valid_until": { "bsonType": ["long", "int", "null"] },
+                    "scheme": { "enum": ["ecdsa", "schnorr"] },
+                    "public_key": { "bsonType": "string" },
+                    "signature": { "bsonType": "string" },
+                    "timestamp": { "bsonType": INTEGER.to_vec() },
+                    "status": {
+                        "enum": ["pending", "confirmed", "rejected", "invalid", "expired"],
+                    },
+                },
+            },
+        ),
+        (
+            "blocks",
+            doc! {
+                "bsonType": "object",
+                "required": [
+                    "version", "index", "timestamp", "transactions", "transaction_hashes",
+                    "previous_hash", "merkle_root", "hash", "nonce", "difficulty",
+                ],
+                "properties": {
+                    "version": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "index": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "timestamp": { "bsonType": INTEGER.to_vec() },
+                    "transactions": { "bsonType": "array", "items": { "bsonType": "objectId" } },
+                    "transaction_hashes": { "bsonType": "array", "items": { "bsonType": "string" } },
+                    "previous_hash": { "bsonType": "string" },
+                    "merkle_root": { "bsonType": "string" },
+                    "hash": { "bsonType": "string" },
+                    "nonce": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                    "difficulty": { "bsonType": INTEGER.to_vec(), "minimum": 0 },
+                },
+            },
+        ),
+    ];
+}
+
+/// Creates the collections and their indexes, and with
+/// `database.schema_validation` set their validators. Existing indexes are
+/// kept, so it runs at every startup. Fails when stored documents break a
+/// unique index, those must be fixed by hand.
+pub async fn bootstrap(db: &Database, setting: &Setting) -> Result<(), String> {
+    let existing = db.list_collection_names().await.map_err(|e| {
+        error!("list collections failed: {}", e);
+        return e.to_string();
+    })?;
+
+    for (collection, _) in indexes() {
+        if !existing.iter().any(|name| name == collection) {
+            db.create_collection(collection).await.map_err(|e| {
+                error!("create collection {} failed: {}", collection, e);
+                return e.to_string();
+            })?;
+        }
+    }
+
+    for (collection, models) in indexes() {
+        db.collection::<Document>(collection)
+            .create_indexes(models)
+            .await
+            .map_err(|e| {
+                error!("create indexes on {} failed: {}", collection, e);
+                return e.to_string();
+            })?;
+    }
+    info!("database indexes are up to date");
+
+    if !setting.database.schema_validation {
+        return Ok(());
+    }
+    for (collection, schema) in validators() {
+        // moderate does not check updates of documents that were already
+        // invalid, so older documents stay updatable
+        db.run_command(doc! {
+            "collMod": collection,
+            "validator": { "$jsonSchema": schema },
+            "validationLevel": "moderate",
+        })
+        .await
+        .map_err(|e| {
+            error!("set validator on {} failed: {}", collection, e);
+            return e.to_string();
+        })?;
+    }
+    info!("database schema validators are set");
+
+    return Ok(());
+}
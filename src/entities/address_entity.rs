@@ -7,18 +7,22 @@ use crate::timer_helper::IntoTimerHelperShared;
 pub struct AddressEntity {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
-    pub public_key: String, // address is a wallet
+    pub address: String, // see crypto_helper::address_from_public_key
+    pub public_key: Option<String>, // unknown for receivers created by a transfer
     pub balance: u64,
+    pub nonce: u64, // next nonce expected from this address
     pub created_at: i64,
     pub updated_at: i64,
 }
 
 impl AddressEntity {
-    pub fn new(public_key: String, t: IntoTimerHelperShared) -> Self {
+    pub fn new(address: String, public_key: Option<String>, t: IntoTimerHelperShared) -> Self {
         return Self {
             id: None,
+            address,
             public_key,
             balance: 0,
+            nonce: 0,
             created_at: t.now(),
             updated_at: t.now(),
         };
@@ -1,37 +1,48 @@
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
-use crate::timer_helper::IntoTimerHelperShared;
+use crate::{header_helper::HEADER_VERSION, timer_helper::IntoTimerHelperShared};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct BlockEntity {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
+    pub version: u32,
     pub index: u64,
     pub timestamp: i64,
     pub transactions: Vec<ObjectId>,
+    pub transaction_hashes: Vec<String>,
     pub previous_hash: String,
+    pub merkle_root: String,
     pub hash: String,
     pub nonce: u64,
+    pub difficulty: u32,
 }
 
 impl BlockEntity {
+    /// Creates an unmined block, `hash` and `nonce` are filled in by
+    /// `pow_helper::mine`.
     pub fn new(
         index: u64,
         transactions: Vec<ObjectId>,
+        transaction_hashes: Vec<String>,
         previous_hash: String,
-        hash: String,
-        nonce: u64,
+        merkle_root: String,
+        difficulty: u32,
         t: IntoTimerHelperShared,
     ) -> Self {
         return Self {
-
             id: None,
+            version: HEADER_VERSION,
             index,
             timestamp: t.now(),
             transactions,
+            transaction_hashes,
             previous_hash,
-            hash,
-            nonce,
-        }
-    } }
\ No newline at end of file
+            merkle_root,
+            hash: String::new(),
+            nonce: 0,
+            difficulty,
+        };
+    }
+}
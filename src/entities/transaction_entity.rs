@@ -1,7 +1,15 @@
-use crate::timer_helper::IntoTimerHelperShared;
+use crate::{
+    crypto_helper,
+    models::transaction_model::{SignatureScheme, TransactionPayload},
+    timer_helper::IntoTimerHelperShared,
+};
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
+/// `from` of the reward transaction a block pays to its miner. Its `amount`
+/// is the newly issued subsidy and its `fee` the fees collected in the block.
+pub const COINBASE_ADDRESS: &str = "coinbase";
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionStatus {
@@ -16,10 +24,21 @@ pub enum TransactionStatus {
 pub struct TransactionEntity {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
+    pub hash: String,
     pub block_hash: Option<String>,
     pub from: String,
     pub to: String,
     pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    /// Unix timestamp after which the transaction can no longer be mined.
+    #[serde(default)]
+    pub valid_until: Option<i64>,
+    #[serde(default)]
+    pub scheme: SignatureScheme,
+    /// Key `signature` verifies against, it must hash to `from`.
+    #[serde(default)]
+    pub public_key: String,
     pub signature: String,
     pub timestamp: i64,
     pub status: TransactionStatus,
@@ -27,22 +46,43 @@ pub struct TransactionEntity {
 
 impl TransactionEntity {
     pub fn new(
-        from: String,
-        to: String,
-        amount: u64,
+        payload: &TransactionPayload,
+        public_key: String,
         signature: String,
         status: TransactionStatus,
         t: IntoTimerHelperShared,
     ) -> Self {
-        return Self {
+        let mut tx = Self {
             id: None,
+            hash: String::new(),
             block_hash: None,
-            from,
-            to,
-            amount,
+            from: payload.from.clone(),
+            to: payload.to.clone(),
+            amount: payload.amount,
+            fee: payload.fee,
+            nonce: payload.nonce,
+            valid_until: payload.valid_until,
+            scheme: payload.scheme,
+            public_key,
             signature,
             timestamp: t.now(),
             status,
         };
+        tx.hash = hex::encode(crypto_helper::transaction_hash(&tx));
+        return tx;
+    }
+
+    /// The payload the sender signed, given the chain it was submitted to.
+    pub fn payload(&self, chain_id: u64) -> TransactionPayload {
+        return TransactionPayload {
+            chain_id,
+            from: self.from.clone(),
+            to: self.to.clone(),
+            amount: self.amount,
+            fee: self.fee,
+            nonce: self.nonce,
+            valid_until: self.valid_until,
+            scheme: self.scheme,
+        };
     }
 }
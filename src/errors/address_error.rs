@@ -8,6 +8,8 @@ pub enum APIAddressError {
     AddressAlreadyExists(String),
     FindAddressError(String),
     UpdateBalanceError(String),
+    InvalidPublicKey(String),
+    SignError(String),
 }
 
 impl IntoErrorResponse for APIAddressError {
@@ -37,6 +39,14 @@ impl IntoErrorResponse for APIAddressError {
                 error: format!("error while update address balance: {}", e),
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             },
+            Self::InvalidPublicKey(addr) => ErrorResponse {
+                error: format!("{} is not a compressed secp256k1 public key", addr),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::SignError(e) => ErrorResponse {
+                error: format!("sign address registration error: {}", e),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
         };
     }
 }
@@ -5,7 +5,13 @@ pub enum APIBlockError {
     InsertBlockError(String),
     FindBlockError(String),
     NotFound(String),
-    InvalidChain(String),
+    SessionError(String),
+    MiningError(String),
+    TransactionNotInBlock(String, String),
+    MinerAddressNotFound(String),
+    MinerAddressNotSet,
+    VerifySignaturesError(String),
+    AmountOverflow(String),
 }
 
 impl IntoErrorResponse for APIBlockError {
@@ -23,9 +29,33 @@ impl IntoErrorResponse for APIBlockError {
                 error: format!("Block not found with hash: {}", hash),
                 status_code: StatusCode::NOT_FOUND,
             },
-            Self::InvalidChain(reason) => ErrorResponse {
-                error: format!("Blockchain invalid: {}", reason),
-                status_code: StatusCode::BAD_REQUEST,
+            Self::SessionError(msg) => ErrorResponse {
+                error: format!("Database session error: {}", msg),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::MiningError(msg) => ErrorResponse {
+                error: format!("Mining block error: {}", msg),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::TransactionNotInBlock(tx_id, hash) => ErrorResponse {
+                error: format!("Transaction {} is not committed by block {}", tx_id, hash),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::MinerAddressNotFound(addr) => ErrorResponse {
+                error: format!("Miner address {} is not registered", addr),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::MinerAddressNotSet => ErrorResponse {
+                error: "Miner address is not set, set mining.miner_address to a registered address to build blocks".to_string(),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::VerifySignaturesError(msg) => ErrorResponse {
+                error: format!("Verify signatures error: {}", msg),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::AmountOverflow(what) => ErrorResponse {
+                error: format!("{} overflows a u64", what),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
             },
         }
     }
@@ -21,4 +21,13 @@ impl IntoResponse for ErrorResponse {
 
 pub trait IntoErrorResponse {
     fn error(&self) -> ErrorResponse;
-}
\ No newline at end of file
+}
+
+impl IntoErrorResponse for ErrorResponse {
+    fn error(&self) -> ErrorResponse {
+        return ErrorResponse {
+            error: self.error.clone(),
+            status_code: self.status_code,
+        };
+    }
+}
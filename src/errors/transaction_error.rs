@@ -10,6 +10,17 @@ pub enum APITransactionError {
     NotFound(ObjectId),
     FindError(String),
     UpdateStatusError(String),
+    NotConfirmed(ObjectId),
+    NonceTooLow(u64, u64),
+    NonceTooHigh(u64, u64),
+    MempoolFull(f64),
+    MempoolFullOfSender(String),
+    Expired(i64, i64),
+    InvalidReceiver(String),
+    SelfTransfer(String),
+    ZeroAmount,
+    PublicKeyMismatch(String),
+    SessionError(String),
 }
 
 impl IntoErrorResponse for APITransactionError {
@@ -23,7 +34,7 @@ impl IntoErrorResponse for APITransactionError {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             },
             Self::InvalidSignature => ErrorResponse {
-                error: format!("invalid signature"),
+                error: "invalid signature".to_string(),
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             },
             Self::VerifySignatureError(e) => ErrorResponse {
@@ -46,6 +57,59 @@ impl IntoErrorResponse for APITransactionError {
                 error: format!("Update transaction status error: {}", e),
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
             },
+            Self::NotConfirmed(tx_id) => ErrorResponse {
+                error: format!("transaction {} is not confirmed in a block", tx_id),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::NonceTooLow(expected, nonce) => ErrorResponse {
+                error: format!("nonce {} was already used, expected {}", nonce, expected),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::NonceTooHigh(expected, nonce) => ErrorResponse {
+                error: format!("nonce {} is out of order, expected {}", nonce, expected),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::MempoolFull(min_fee_rate) => ErrorResponse {
+                error: format!(
+                    "mempool is full, fee rate must be above {} per byte",
+                    min_fee_rate
+                ),
+                status_code: StatusCode::SERVICE_UNAVAILABLE,
+            },
+            Self::MempoolFullOfSender(sender) => ErrorResponse {
+                error: format!(
+                    "mempool is full and only holds transactions from {}",
+                    sender
+                ),
+                status_code: StatusCode::SERVICE_UNAVAILABLE,
+            },
+            Self::Expired(valid_until, now) => ErrorResponse {
+                error: format!(
+                    "transaction expired at {}, current time is {}",
+                    valid_until, now
+                ),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::InvalidReceiver(receiver) => ErrorResponse {
+                error: format!("receiver {} is not a valid address", receiver),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::SelfTransfer(address) => ErrorResponse {
+                error: format!("address {} cannot send to itself", address),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::ZeroAmount => ErrorResponse {
+                error: "amount must be greater than zero".to_string(),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::PublicKeyMismatch(address) => ErrorResponse {
+                error: format!("public key does not belong to address {}", address),
+                status_code: StatusCode::BAD_REQUEST,
+            },
+            Self::SessionError(msg) => ErrorResponse {
+                error: format!("Database session error: {}", msg),
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            },
         };
     }
 }
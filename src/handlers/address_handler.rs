@@ -4,38 +4,43 @@ use axum::Json;
 use axum::extract::Path;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use secp256k1::rand::rngs::OsRng;
-use secp256k1::{PublicKey, Secp256k1, SecretKey};
 use serde::Deserialize;
 use serde_json::json;
 use std::sync::Arc;
 
-fn generate_keypair() -> (SecretKey, PublicKey) {
-    let secp = Secp256k1::new();
-    let (secret_key, public_key) = secp.generate_keypair(&mut OsRng);
-    return (secret_key, public_key);
+pub async fn handler_create_address(
+    Json(payload): Json<InsertAddress>,
+    address_usecase: Arc<AddressUsecase>,
+) -> impl IntoResponse {
+    let address = match address_usecase.create_new_address(payload).await {
+        Ok(address) => address,
+        Err(e) => return e.error().into_response(),
+    };
+
+    return (
+        StatusCode::CREATED,
+        Json(json!({
+            "object_id": address.id,
+            "address": address.address,
+            "public_key": address.public_key,
+        })),
+    )
+        .into_response();
 }
 
-pub async fn handler_create_address(address_usecase: Arc<AddressUsecase>) -> impl IntoResponse {
-    let (secret_key, public_key) = generate_keypair();
-    let object_id = match address_usecase
-        .create_new_address(InsertAddress {
-            public_key: public_key.to_string(),
-        })
-        .await
-    {
-        Ok(id) => id,
+pub async fn handler_generate_address(address_usecase: Arc<AddressUsecase>) -> impl IntoResponse {
+    let (address, secret_key) = match address_usecase.generate_address().await {
+        Ok(r) => r,
         Err(e) => return e.error().into_response(),
     };
 
-    let secret_key_hex = hex::encode(secret_key.secret_bytes());
-
     return (
         StatusCode::CREATED,
         Json(json!({
-            "object_id": object_id,
-            "public_key": public_key.to_string(),
-            "secret_key": secret_key_hex,
+            "object_id": address.id,
+            "address": address.address,
+            "public_key": address.public_key,
+            "secret_key": secret_key,
         })),
     )
         .into_response();
@@ -47,12 +52,12 @@ pub struct DepositRequest {
 }
 
 pub async fn handler_deposit_coin(
-    Path(public_key): Path<String>,
+    Path(address): Path<String>,
     Json(payload): Json<DepositRequest>,
     address_usecase: Arc<AddressUsecase>,
 ) -> impl IntoResponse {
     let deposit_info = CoinWithAddress {
-        public_key,
+        address,
         amount: payload.amount,
     };
     match address_usecase.deposit_coin(deposit_info).await {
@@ -68,3 +73,21 @@ pub async fn handler_deposit_coin(
     )
         .into_response();
 }
+
+pub async fn handler_get_address(
+    Path(address): Path<String>,
+    address_usecase: Arc<AddressUsecase>,
+) -> impl IntoResponse {
+    let address = match address_usecase.get_address(address).await {
+        Ok(address) => address,
+        Err(e) => return e.error().into_response(),
+    };
+
+    return (
+        StatusCode::OK,
+        Json(json!({
+            "address": address,
+        })),
+    )
+        .into_response();
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use axum::{Json, extract::Path, http::StatusCode, response::IntoResponse};
+use bson::oid::ObjectId;
 use serde_json::json;
 
 use crate::usecases::block_usecase::BlockUsecase;
@@ -35,9 +36,52 @@ pub async fn handler_get_block_by_hash(
     (StatusCode::OK, Json(result)).into_response()
 }
 
+pub async fn handler_get_transaction_proof(
+    Path(tx_id): Path<ObjectId>,
+    block_usecase: Arc<BlockUsecase>,
+) -> impl IntoResponse {
+    let result = match block_usecase.get_transaction_proof(tx_id).await {
+        Ok(proof) => json!({ "success": true, "proof": proof }),
+        Err(e) => return e.error().into_response(),
+    };
+
+    (StatusCode::OK, Json(result)).into_response()
+}
+
+pub async fn handler_get_total_supply(block_usecase: Arc<BlockUsecase>) -> impl IntoResponse {
+    let result = match block_usecase.get_total_supply().await {
+        Ok(supply) => json!({ "success": true, "total_supply": supply }),
+        Err(e) => return e.error().into_response(),
+    };
+
+    (StatusCode::OK, Json(result)).into_response()
+}
+
 pub async fn handler_verify_chain(block_usecase: Arc<BlockUsecase>) -> impl IntoResponse {
-    let result = match block_usecase.verify_chain().await {
-        Ok(_) => json!({ "success": true }),
+    let report = match block_usecase.verify_chain().await {
+        Ok(report) => report,
+        Err(e) => return e.error().into_response(),
+    };
+
+    let status_code = if report.valid {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+
+    (
+        status_code,
+        Json(json!({ "success": report.valid, "report": report })),
+    )
+        .into_response()
+}
+
+pub async fn handler_verify_block_signatures(
+    Path(hash): Path<String>,
+    block_usecase: Arc<BlockUsecase>,
+) -> impl IntoResponse {
+    let result = match block_usecase.verify_block_signatures(hash).await {
+        Ok(report) => json!({ "success": true, "report": report }),
         Err(e) => return e.error().into_response(),
     };
 
@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use axum::{Json, http::StatusCode, response::IntoResponse};
+use serde_json::json;
+
+use crate::usecases::mempool_usecase::MempoolUsecase;
+
+pub async fn handler_get_mempool_stats(mempool_usecase: Arc<MempoolUsecase>) -> impl IntoResponse {
+    let result = match mempool_usecase.stats().await {
+        Ok(stats) => json!({ "success": true, "stats": stats }),
+        Err(e) => return e.error().into_response(),
+    };
+
+    (StatusCode::OK, Json(result)).into_response()
+}
@@ -1,3 +1,4 @@
 pub mod address_handler;
 pub mod transaction_handler;
-pub mod block_handler;
\ No newline at end of file
+pub mod block_handler;
+pub mod mempool_handler;
\ No newline at end of file
@@ -0,0 +1,174 @@
+use bip39::{Language, Mnemonic};
+use hmac::{Hmac, Mac};
+use secp256k1::{PublicKey, Scalar, SecretKey};
+use sha2::Sha512;
+
+use crate::crypto_helper;
+
+/// Indexes at or above this derive hardened children.
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// BIP44 purpose used by every derivation path of this chain.
+pub const PURPOSE: u32 = 44;
+
+/// BIP44 coin type of this chain, `0x7263` ("rc"). Unregistered, chosen so
+/// seeds shared with other wallets never derive the same keys.
+pub const COIN_TYPE: u32 = 0x7263;
+
+/// A BIP32 extended private key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedKey {
+    pub secret_key: SecretKey,
+    pub chain_code: [u8; 32],
+    pub depth: u8,
+}
+
+/// A key derived from a seed along with where it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedKey {
+    pub path: String,
+    pub address: String,
+    pub public_key: String,
+    pub secret_key: String,
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data);
+    return mac.finalize().into_bytes().into();
+}
+
+fn split(i: [u8; 64]) -> ([u8; 32], [u8; 32]) {
+    let mut left = [0u8; 32];
+    let mut right = [0u8; 32];
+    left.copy_from_slice(&i[..32]);
+    right.copy_from_slice(&i[32..]);
+    return (left, right);
+}
+
+/// A new English mnemonic of 12, 15, 18, 21 or 24 words.
+pub fn generate_mnemonic(words: usize) -> Result<String, String> {
+    if !words.is_multiple_of(3) || !(12..=24).contains(&words) {
+        return Err(format!(
+            "a mnemonic has 12 to 24 words in steps of 3, got {}",
+            words
+        ));
+    }
+    let entropy: [u8; 32] = rand::random();
+    let mnemonic = Mnemonic::from_entropy_in(Language::English, &entropy[..words / 3 * 4])
+        .map_err(|e| e.to_string())?;
+    return Ok(mnemonic.to_string());
+}
+
+/// The 64 byte BIP39 seed of an English mnemonic, checking its checksum.
+pub fn seed_from_mnemonic(phrase: &str, passphrase: &str) -> Result<[u8; 64], String> {
+    let mnemonic = Mnemonic::parse_in(Language::English, phrase)
+        .map_err(|e| format!("invalid mnemonic: {}", e))?;
+    return Ok(mnemonic.to_seed(passphrase));
+}
+
+impl ExtendedKey {
+    pub fn master(seed: &[u8]) -> Result<Self, String> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(format!("seed must be 16 to 64 bytes, got {}", seed.len()));
+        }
+        let (key, chain_code) = split(hmac_sha512(b"Bitcoin seed", seed));
+        let secret_key = SecretKey::from_byte_array(&key).map_err(|e| e.to_string())?;
+        return Ok(Self {
+            secret_key,
+            chain_code,
+            depth: 0,
+        });
+    }
+
+    /// The child at `index`, hardened when `index >= HARDENED`.
+    pub fn derive_child(&self, index: u32) -> Result<Self, String> {
+        let depth = match self.depth.checked_add(1) {
+            Some(depth) => depth,
+            None => {
+                return Err(format!(
+                    "key is already at the maximum depth {}",
+                    self.depth
+                ));
+            }
+        };
+        let mut data = Vec::with_capacity(37);
+        if index >= HARDENED {
+            data.push(0);
+            data.extend_from_slice(&self.secret_key.secret_bytes());
+        } else {
+            let public_key =
+                PublicKey::from_secret_key(&crypto_helper::SECP256K1, &self.secret_key);
+            data.extend_from_slice(&public_key.serialize());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+
+        let (tweak, chain_code) = split(hmac_sha512(&self.chain_code, &data));
+        // out of range tweaks are astronomically unlikely, BIP32 skips to
+        // the next index and so can the caller
+        let tweak = Scalar::from_be_bytes(tweak).map_err(|e| e.to_string())?;
+        let secret_key = self
+            .secret_key
+            .add_tweak(&tweak)
+            .map_err(|e| e.to_string())?;
+        return Ok(Self {
+            secret_key,
+            chain_code,
+            depth,
+        });
+    }
+
+    pub fn derive_path(&self, path: &str) -> Result<Self, String> {
+        let mut key = self.clone();
+        for index in parse_path(path)? {
+            key = key.derive_child(index)?;
+        }
+        return Ok(key);
+    }
+}
+
+/// Parses a path such as `m/44'/0'/0'/0/7`, hardened steps marked with `'`
+/// or `h`.
+pub fn parse_path(path: &str) -> Result<Vec<u32>, String> {
+    let mut steps = path.split('/');
+    if steps.next() != Some("m") {
+        return Err(format!("path {} must start with m", path));
+    }
+    return steps
+        .map(|step| {
+            let (number, hardened) = match step.strip_suffix(['\'', 'h']) {
+                Some(number) => (number, true),
+                None => (step, false),
+            };
+            let index: u32 = number
+                .parse()
+                .map_err(|_| format!("invalid step {} in path {}", step, path))?;
+            if index >= HARDENED {
+                return Err(format!("step {} in path {} is out of range", step, path));
+            }
+            return Ok(if hardened { index + HARDENED } else { index });
+        })
+        .collect();
+}
+
+/// The path of the `index`th receiving address of `account`,
+/// `m/44'/29283'/account'/0/index`.
+pub fn address_path(account: u32, index: u32) -> String {
+    return format!("m/{}'/{}'/{}'/0/{}", PURPOSE, COIN_TYPE, account, index);
+}
+
+/// Derives the `index`th address of `account` from a BIP39 seed.
+pub fn derive_address(seed: &[u8], account: u32, index: u32) -> Result<DerivedKey, String> {
+    let path = address_path(account, index);
+    let key = ExtendedKey::master(seed)?.derive_path(&path)?;
+    let secret_key = hex::encode(key.secret_key.secret_bytes());
+    let public_key =
+        crypto_helper::public_key_from_secret_key(&secret_key).map_err(|e| e.to_string())?;
+    let address = crypto_helper::address_from_public_key(&public_key).map_err(|e| e.to_string())?;
+    return Ok(DerivedKey {
+        path,
+        address,
+        public_key,
+        secret_key,
+    });
+}
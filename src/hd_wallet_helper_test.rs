@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use crate::hd_wallet_helper::{
+        ExtendedKey, HARDENED, derive_address, generate_mnemonic, parse_path, seed_from_mnemonic,
+    };
+
+    const ABANDON: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn bip39_seed_vector_test() {
+        let seed = seed_from_mnemonic(ABANDON, "TREZOR").unwrap();
+
+        assert_eq!(
+            hex::encode(seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn bip39_bad_checksum_test() {
+        let phrase = ABANDON.replace("about", "abandon");
+
+        assert!(seed_from_mnemonic(&phrase, "").is_err());
+    }
+
+    #[test]
+    fn generate_mnemonic_test() {
+        let phrase = generate_mnemonic(24).unwrap();
+
+        assert_eq!(phrase.split(' ').count(), 24);
+        assert!(seed_from_mnemonic(&phrase, "").is_ok());
+        assert!(generate_mnemonic(13).is_err());
+    }
+
+    #[test]
+    fn bip32_vector_test() {
+        // test vector 1 of BIP32
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedKey::master(&seed).unwrap();
+        let child = master.derive_path("m/0'/1/2'/2/1000000000").unwrap();
+
+        assert_eq!(
+            hex::encode(master.secret_key.secret_bytes()),
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        assert_eq!(
+            hex::encode(child.secret_key.secret_bytes()),
+            "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8"
+        );
+        assert_eq!(
+            hex::encode(child.chain_code),
+            "c783e67b921d2beb8f6b389cc646d7263b4145701dadd2161548a8b078e65e9e"
+        );
+        assert_eq!(child.depth, 5);
+    }
+
+    #[test]
+    fn derive_path_too_deep_test() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedKey::master(&seed).unwrap();
+
+        let deepest = master
+            .derive_path(&format!("m{}", "/0".repeat(255)))
+            .unwrap();
+        assert_eq!(deepest.depth, 255);
+        assert!(deepest.derive_child(0).is_err());
+        assert!(
+            master
+                .derive_path(&format!("m{}", "/0".repeat(256)))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_path_test() {
+        assert_eq!(
+            parse_path("m/44'/7h/0").unwrap(),
+            vec![44 + HARDENED, 7 + HARDENED, 0]
+        );
+        assert!(parse_path("44'/0").is_err());
+        assert!(parse_path("m/x").is_err());
+        assert!(parse_path("m/2147483648").is_err());
+    }
+
+    #[test]
+    fn derive_address_test() {
+        let seed = seed_from_mnemonic(ABANDON, "").unwrap();
+        let first = derive_address(&seed, 0, 0).unwrap();
+        let second = derive_address(&seed, 0, 1).unwrap();
+
+        assert_eq!(first.path, "m/44'/29283'/0'/0/0");
+        assert_eq!(first.address, "RBabsnM9YSF32ddeJEHnRjVFmDRRBXP5Tf");
+        assert_ne!(first.address, second.address);
+        assert_eq!(derive_address(&seed, 0, 0).unwrap(), first);
+    }
+}
@@ -0,0 +1,83 @@
+use sha2::{Digest, Sha256};
+
+use crate::entities::block_entity::BlockEntity;
+
+pub const HEADER_VERSION: u32 = 1;
+/// Version of the blocks older nodes wrote, their hash was computed over a
+/// header that is no longer known.
+pub const LEGACY_HEADER_VERSION: u32 = 0;
+pub const HEADER_SIZE: usize = 96;
+
+/// Block header as it is hashed. All integers are big-endian:
+///
+/// | offset | size | field         |
+/// |--------|------|---------------|
+/// | 0      | 4    | version (u32) |
+/// | 4      | 8    | index (u64)   |
+/// | 12     | 32   | previous hash |
+/// | 44     | 32   | merkle root   |
+/// | 76     | 8    | timestamp (i64, unix seconds) |
+/// | 84     | 4    | difficulty (u32) |
+/// | 88     | 8    | nonce (u64)   |
+///
+/// The block hash is the SHA-256 of these 96 bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub index: u64,
+    pub previous_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub timestamp: i64,
+    pub difficulty: u32,
+    pub nonce: u64,
+}
+
+impl BlockHeader {
+    pub fn from_block(block: &BlockEntity) -> Result<Self, String> {
+        if block.version != HEADER_VERSION {
+            return Err(format!("unsupported header version {}", block.version));
+        }
+
+        return Ok(Self {
+            version: block.version,
+            index: block.index,
+            previous_hash: decode_hash(&block.previous_hash)?,
+            merkle_root: decode_hash(&block.merkle_root)?,
+            timestamp: block.timestamp,
+            difficulty: block.difficulty,
+            nonce: block.nonce,
+        });
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&self.version.to_be_bytes());
+        bytes[4..12].copy_from_slice(&self.index.to_be_bytes());
+        bytes[12..44].copy_from_slice(&self.previous_hash);
+        bytes[44..76].copy_from_slice(&self.merkle_root);
+        bytes[76..84].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[84..88].copy_from_slice(&self.difficulty.to_be_bytes());
+        bytes[88..96].copy_from_slice(&self.nonce.to_be_bytes());
+        return bytes;
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        return Sha256::digest(self.to_bytes()).into();
+    }
+}
+
+/// Decodes a hex hash, an empty string (the genesis previous hash) is all zeros.
+pub fn decode_hash(hash: &str) -> Result<[u8; 32], String> {
+    if hash.is_empty() {
+        return Ok([0u8; 32]);
+    }
+
+    let bytes = hex::decode(hash).map_err(|e| e.to_string())?;
+    return bytes
+        .try_into()
+        .map_err(|_| format!("hash {} is not 32 bytes", hash));
+}
+
+pub fn calculate_hash(block: &BlockEntity) -> Result<String, String> {
+    return Ok(hex::encode(BlockHeader::from_block(block)?.hash()));
+}
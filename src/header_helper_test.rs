@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use crate::{
+        crypto_helper::merkle_root,
+        header_helper::{BlockHeader, HEADER_SIZE, decode_hash},
+    };
+
+    #[test]
+    fn genesis_header_vector_test() {
+        let header = BlockHeader {
+            version: 1,
+            index: 1,
+            previous_hash: [0u8; 32],
+            merkle_root: [0u8; 32],
+            timestamp: 0,
+            difficulty: 0,
+            nonce: 0,
+        };
+
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE);
+        assert_eq!(hex::encode(&bytes[..12]), "000000010000000000000001");
+        assert_eq!(
+            hex::encode(header.hash()),
+            "85043c5493c6fd794f08f5d27a023d43b2b3a10b0a9fe3f25bf878a74f87532e"
+        );
+    }
+
+    #[test]
+    fn header_vector_test() {
+        let header = BlockHeader {
+            version: 1,
+            index: 42,
+            previous_hash: decode_hash(
+                "0000111111111111111111111111111111111111111111111111111111111111",
+            )
+            .unwrap(),
+            merkle_root: decode_hash(
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            )
+            .unwrap(),
+            timestamp: 1700000000,
+            difficulty: 16,
+            nonce: 123456789,
+        };
+
+        assert_eq!(
+            hex::encode(header.to_bytes()),
+            concat!(
+                "00000001",
+                "000000000000002a",
+                "0000111111111111111111111111111111111111111111111111111111111111",
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "000000006553f100",
+                "00000010",
+                "00000000075bcd15",
+            )
+        );
+        assert_eq!(
+            hex::encode(header.hash()),
+            "63ed4dd50880d0dfb046b5e2d303c7bcae8662850b67d89e5e8e598f066d24f3"
+        );
+    }
+
+    #[test]
+    fn merkle_root_vector_test() {
+        let leaves: Vec<[u8; 32]> = [1u8, 2, 3]
+            .iter()
+            .map(|i| {
+                let mut bytes = [0u8; 12];
+                bytes[11] = *i;
+                Sha256::digest(bytes).into()
+            })
+            .collect();
+
+        assert_eq!(merkle_root(&[]), [0u8; 32]);
+        assert_eq!(
+            hex::encode(merkle_root(&leaves[..1])),
+            "6c387a93e375e2f742fd9d70c4d9758e415197a1f5836f2ad95fb82864a04681"
+        );
+        assert_eq!(
+            hex::encode(merkle_root(&leaves)),
+            "a0b35faf649f048ebb3d9720d06394c013128a93d7b8b22c0cca887e6b3d1093"
+        );
+    }
+
+    #[test]
+    fn decode_hash_test() {
+        assert_eq!(decode_hash("").unwrap(), [0u8; 32]);
+        assert!(decode_hash("00ff").is_err());
+        assert!(decode_hash("zz").is_err());
+    }
+}
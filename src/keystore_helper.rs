@@ -0,0 +1,204 @@
+use std::{fs, io::Write, path::Path};
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
+use pbkdf2::pbkdf2_hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::crypto_helper;
+
+pub const KEYSTORE_VERSION: u32 = 1;
+pub const KEYSTORE_KDF: &str = "pbkdf2-sha256";
+pub const KEYSTORE_CIPHER: &str = "aes-256-gcm";
+/// Default PBKDF2 work factor for new keystores.
+pub const PBKDF2_ITERATIONS: u32 = 600_000;
+/// Work factors above this are refused so a crafted file can't stall
+/// whoever unlocks it.
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// A secp256k1 secret key encrypted under a password, see the Keystore
+/// section of the README. The address and public key are stored in clear
+/// and authenticated with the ciphertext.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub address: String,
+    pub public_key: String,
+    pub crypto: KeystoreCrypto,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub kdf: String,
+    pub iterations: u32,
+    pub salt: String,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+fn derive_key(password: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut key);
+    return key;
+}
+
+/// Bytes authenticated alongside the ciphertext.
+fn associated_data(address: &str, public_key: &str) -> Vec<u8> {
+    return format!("{}:{}", address, public_key).into_bytes();
+}
+
+fn check_iterations(iterations: u32) -> Result<(), String> {
+    if iterations == 0 || iterations > MAX_PBKDF2_ITERATIONS {
+        return Err(format!(
+            "iterations must be between 1 and {}, got {}",
+            MAX_PBKDF2_ITERATIONS, iterations
+        ));
+    }
+    return Ok(());
+}
+
+/// Encrypts the hex `secret_key` under `password`, deriving the key with
+/// `iterations` rounds of PBKDF2 and a random salt and nonce.
+pub fn create(secret_key: &str, password: &str, iterations: u32) -> Result<Keystore, String> {
+    return create_with(
+        secret_key,
+        password,
+        iterations,
+        rand::random(),
+        rand::random(),
+    );
+}
+
+/// `create` with a caller chosen salt and nonce, for test vectors. A nonce
+/// must never be reused with the same password and salt.
+pub fn create_with(
+    secret_key: &str,
+    password: &str,
+    iterations: u32,
+    salt: [u8; 16],
+    nonce: [u8; 12],
+) -> Result<Keystore, String> {
+    check_iterations(iterations)?;
+    let public_key = crypto_helper::public_key_from_secret_key(secret_key)
+        .map_err(|e| format!("invalid secret key: {}", e))?;
+    let address = crypto_helper::address_from_public_key(&public_key).map_err(|e| e.to_string())?;
+    let secret = hex::decode(secret_key).map_err(|e| e.to_string())?;
+
+    let key = derive_key(password, &salt, iterations);
+    let ciphertext = Aes256Gcm::new(&key.into())
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &secret,
+                aad: &associated_data(&address, &public_key),
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    return Ok(Keystore {
+        version: KEYSTORE_VERSION,
+        address,
+        public_key,
+        crypto: KeystoreCrypto {
+            kdf: KEYSTORE_KDF.to_string(),
+            iterations,
+            salt: hex::encode(salt),
+            cipher: KEYSTORE_CIPHER.to_string(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        },
+    });
+}
+
+/// Decrypts the keystore and returns the hex secret key.
+pub fn unlock(keystore: &Keystore, password: &str) -> Result<String, String> {
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(format!("unsupported keystore version {}", keystore.version));
+    }
+    let crypto = &keystore.crypto;
+    if crypto.kdf != KEYSTORE_KDF || crypto.cipher != KEYSTORE_CIPHER {
+        return Err(format!("unsupported {} with {}", crypto.kdf, crypto.cipher));
+    }
+    check_iterations(crypto.iterations)?;
+
+    let salt = hex::decode(&crypto.salt).map_err(|e| e.to_string())?;
+    let nonce = hex::decode(&crypto.nonce).map_err(|e| e.to_string())?;
+    let ciphertext = hex::decode(&crypto.ciphertext).map_err(|e| e.to_string())?;
+    if nonce.len() != 12 {
+        return Err(format!("nonce is {} bytes, expected 12", nonce.len()));
+    }
+
+    let key = derive_key(password, &salt, crypto.iterations);
+    let secret = Aes256Gcm::new(&key.into())
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: &associated_data(&keystore.address, &keystore.public_key),
+            },
+        )
+        .map_err(|_| "wrong password or corrupted keystore".to_string())?;
+
+    let secret_key = hex::encode(secret);
+    match crypto_helper::public_key_from_secret_key(&secret_key) {
+        Ok(public_key) if public_key == keystore.public_key => {}
+        _ => return Err("secret key does not match the keystore public key".to_string()),
+    };
+    return Ok(secret_key);
+}
+
+/// Re-encrypts the keystore under `new_password` with a fresh salt and
+/// nonce, keeping its work factor.
+pub fn change_password(
+    keystore: &Keystore,
+    old_password: &str,
+    new_password: &str,
+) -> Result<Keystore, String> {
+    let secret_key = unlock(keystore, old_password)?;
+    return create(&secret_key, new_password, keystore.crypto.iterations);
+}
+
+pub fn read_file(path: &Path) -> Result<Keystore, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("read {:?}: {}", path, e))?;
+    return serde_json::from_str(&text).map_err(|e| format!("parse {:?}: {}", path, e));
+}
+
+/// Writes `value`, a keystore or a wallet of them, as JSON to a file only
+/// its owner can read on unix. A new file is created with that mode and an
+/// existing one is restricted before anything is written, so the secret is
+/// never readable by others.
+pub fn write_file<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("open {:?}: {}", path, e))?;
+
+    // the mode only applies when the file is created
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("chmod {:?}: {}", path, e))?;
+    }
+    return file
+        .write_all(text.as_bytes())
+        .map_err(|e| format!("write {:?}: {}", path, e));
+}
+
+/// Reads and unlocks a keystore file, for a node that signs with a key
+/// kept on disk.
+pub fn unlock_file(path: &Path, password: &str) -> Result<String, String> {
+    return unlock(&read_file(path)?, password);
+}
@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+    use crate::keystore_helper::{
+        change_password, create, create_with, read_file, unlock, unlock_file, write_file,
+    };
+
+    // keeps tests fast, wallets use PBKDF2_ITERATIONS
+    const ITERATIONS: u32 = 1_000;
+    const SECRET_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+    #[test]
+    fn keystore_round_trip_test() {
+        let keystore = create(SECRET_KEY, "correct horse", ITERATIONS).unwrap();
+
+        assert_eq!(keystore.address, "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh");
+        assert_eq!(unlock(&keystore, "correct horse").unwrap(), SECRET_KEY);
+    }
+
+    #[test]
+    fn keystore_wrong_password_test() {
+        let keystore = create(SECRET_KEY, "correct horse", ITERATIONS).unwrap();
+
+        assert!(unlock(&keystore, "battery staple").is_err());
+    }
+
+    #[test]
+    fn keystore_swapped_address_test() {
+        let mut keystore = create(SECRET_KEY, "correct horse", ITERATIONS).unwrap();
+        keystore.address = String::from("RLkZJhcqT2ac31oqBeLvo6egULQR54LNxb");
+
+        assert!(unlock(&keystore, "correct horse").is_err());
+    }
+
+    #[test]
+    fn keystore_vector_test() {
+        let keystore = create_with(
+            SECRET_KEY,
+            "correct horse",
+            ITERATIONS,
+            [7u8; 16],
+            [9u8; 12],
+        )
+        .unwrap();
+
+        assert_eq!(keystore.crypto.salt, "07070707070707070707070707070707");
+        assert_eq!(keystore.crypto.nonce, "090909090909090909090909");
+        assert_eq!(
+            keystore.crypto.ciphertext,
+            "5605666d37ce5a0bbe12c81bd95cdcdff52fd72731d6bbdcf450d91eaedfa4cb4cb1cd9838515aa58a4416134a301d69"
+        );
+        assert_eq!(unlock(&keystore, "correct horse").unwrap(), SECRET_KEY);
+    }
+
+    #[test]
+    fn keystore_change_password_test() {
+        let keystore = create(SECRET_KEY, "correct horse", ITERATIONS).unwrap();
+        let changed = change_password(&keystore, "correct horse", "battery staple").unwrap();
+
+        assert_eq!(changed.address, keystore.address);
+        assert_eq!(changed.crypto.iterations, ITERATIONS);
+        assert_ne!(changed.crypto.salt, keystore.crypto.salt);
+        assert!(unlock(&changed, "correct horse").is_err());
+        assert_eq!(unlock(&changed, "battery staple").unwrap(), SECRET_KEY);
+        assert!(change_password(&keystore, "battery staple", "x").is_err());
+    }
+
+    #[test]
+    fn keystore_rejects_excessive_iterations_test() {
+        let mut keystore = create(SECRET_KEY, "correct horse", ITERATIONS).unwrap();
+        keystore.crypto.iterations = u32::MAX;
+
+        assert!(unlock(&keystore, "correct horse").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keystore_file_owner_only_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!(
+            "rust_chain_keystore_{}.json",
+            bson::oid::ObjectId::new()
+        ));
+        let mode = |path: &std::path::Path| {
+            return std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        };
+        let keystore = create(SECRET_KEY, "correct horse", ITERATIONS).unwrap();
+
+        write_file(&path, &keystore).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(unlock_file(&path, "correct horse").unwrap(), SECRET_KEY);
+
+        // an existing file readable by others is restricted too
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_file(&path, &keystore).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(read_file(&path).unwrap().address, keystore.address);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
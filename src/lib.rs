@@ -7,4 +7,19 @@ pub mod repository;
 pub mod models;
 pub mod timer_helper;
 pub mod handlers;
-pub mod crypto_helper;
\ No newline at end of file
+pub mod app;
+pub mod crypto_helper;
+pub mod crypto_helper_test;
+pub mod pow_helper;
+pub mod pow_helper_test;
+pub mod chain_helper;
+pub mod chain_helper_test;
+pub mod header_helper;
+pub mod header_helper_test;
+pub mod keystore_helper;
+pub mod keystore_helper_test;
+pub mod hd_wallet_helper;
+pub mod hd_wallet_helper_test;
+pub mod signature_helper;
+pub mod signature_helper_test;
+pub mod app_test;
@@ -1,43 +1,36 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, process::ExitCode, sync::Arc};
 
-use axum::{
-    Router,
-    http::Method,
-    routing::{get, patch, post},
-};
+use clap::{Parser, Subcommand};
 use rust_chain::{
-    database::database,
-    handlers::{
-        address_handler::{handler_create_address, handler_deposit_coin},
-        block_handler::{
-            handler_build_block, handler_get_block_by_hash, handler_get_latest_block,
-            handler_verify_chain,
-        },
-        transaction_handler::{
-            handler_confirm_transaction, handler_create_transaction,
-            handler_get_pending_transactions, handler_get_transaction_by_id,
-            handler_get_transactions_by_address,
-        },
-    },
-    repository::{
-        address_repository::MongoAddressRepository, block_repository::MongoBlockRepository,
-        transaction_repository::MongoTransactionRepository,
-    },
-    setting::Setting,
+    app::{self, Repositories},
+    database::{database, migrations},
+    setting::{Setting, StorageBackend},
     timer_helper::TimerHelper,
-    usecases::{
-        address_usecase::AddressUsecase, block_usecase::BlockUsecase,
-        transaction_usecase::TransactionUsecase,
-    },
 };
-use tower_http::{
-    cors::{Any, CorsLayer},
-    trace::TraceLayer,
-};
-use tracing::info;
+use tracing::{error, info};
+
+#[derive(Parser)]
+#[command(about = "Run a rust_chain node")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the HTTP API, the default.
+    Serve,
+    /// Apply the pending migrations of the Mongo store and exit.
+    Migrate {
+        /// Only list the pending migrations and the documents they match.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::DEBUG)
         .init();
@@ -45,46 +38,25 @@ async fn main() {
     let setting = Setting::new().unwrap();
     info!("Setting has been loaded.");
 
-    let db = database::db_connect(Arc::clone(&setting)).await.unwrap();
-    info!("database connect successfully");
+    return match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            serve(setting).await;
+            ExitCode::SUCCESS
+        }
+        Command::Migrate { dry_run } => match migrate(setting, dry_run).await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                error!("migrate failed: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+    };
+}
 
+async fn serve(setting: Arc<Setting>) {
+    let repositories = Repositories::open(Arc::clone(&setting)).await.unwrap();
     let timer_helper = TimerHelper::Directly.creation();
-
-    let address_repository = MongoAddressRepository::creation(db.clone());
-    let address_usecase =
-        AddressUsecase::creation(Arc::clone(&address_repository), Arc::clone(&timer_helper));
-
-    let transaction_repository = MongoTransactionRepository::creation(db.clone());
-    let transaction_usecase = TransactionUsecase::creation(
-        Arc::clone(&transaction_repository),
-        Arc::clone(&address_repository),
-        Arc::clone(&timer_helper),
-    );
-
-    let block_repository = MongoBlockRepository::creation(db.clone());
-    let block_usecase = BlockUsecase::creation(
-        Arc::clone(&block_repository),
-        Arc::clone(&transaction_usecase),
-        Arc::clone(&address_usecase),
-        Arc::clone(&timer_helper),
-    );
-
-    let app = Router::new()
-        .layer(
-            CorsLayer::new()
-                .allow_methods([
-                    Method::GET,
-                    Method::POST,
-                    Method::PUT,
-                    Method::PATCH,
-                    Method::DELETE,
-                ])
-                .allow_origin(Any),
-        )
-        .layer(TraceLayer::new_for_http())
-        .merge(address_routes(Arc::clone(&address_usecase)))
-        .merge(transaction_routes(Arc::clone(&transaction_usecase)))
-        .merge(block_routes(Arc::clone(&block_usecase)));
+    let app = app::router(Arc::clone(&setting), repositories, timer_helper);
 
     let addr: SocketAddr = SocketAddr::from(([0, 0, 0, 0], setting.server.port as u16));
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
@@ -93,91 +65,23 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-fn address_routes(address_usecase: Arc<AddressUsecase>) -> Router {
-    return Router::<()>::new()
-        .route(
-            "/addresses",
-            post({
-                let usecase = Arc::clone(&address_usecase);
-                move || handler_create_address(usecase)
-            }),
-        )
-        .route(
-            "/addresses/{public_key}",
-            patch({
-                let usecase = Arc::clone(&address_usecase);
-                move |path, body| handler_deposit_coin(path, body, usecase)
-            }),
-        );
-}
-
-fn transaction_routes(transaction_usecase: Arc<TransactionUsecase>) -> Router {
-    return Router::<()>::new()
-        .route(
-            "/transactions",
-            post({
-                let usecase = Arc::clone(&transaction_usecase);
-                move |body| handler_create_transaction(body, usecase)
-            }),
-        )
-        .route(
-            "/transactions/{id}",
-            get({
-                let usecase = Arc::clone(&transaction_usecase);
-                move |path| handler_get_transaction_by_id(path, usecase)
-            }),
-        )
-        .route(
-            "/addresses/{address}/transactions",
-            get({
-                let usecase = Arc::clone(&transaction_usecase);
-                move |path| handler_get_transactions_by_address(path, usecase)
-            }),
-        )
-        .route(
-            "/transactions/pending",
-            get({
-                let usecase = Arc::clone(&transaction_usecase);
-                move || handler_get_pending_transactions(usecase)
-            }),
-        )
-        .route(
-            "/transactions/{id}/confirm",
-            patch({
-                let usecase = Arc::clone(&transaction_usecase);
-                move |path, body| handler_confirm_transaction(path, body, usecase)
-            }),
-        );
-}
-
-fn block_routes(block_usecase: Arc<BlockUsecase>) -> Router {
-    return Router::<()>::new()
-        .route(
-            "/blocks",
-            post({
-                let usecase = Arc::clone(&block_usecase);
-                move || handler_build_block(usecase)
-            }),
-        )
-        .route(
-            "/blocks/latest",
-            get({
-                let usecase = Arc::clone(&block_usecase);
-                move || handler_get_latest_block(usecase)
-            }),
-        )
-        .route(
-            "/blocks/{hash}",
-            get({
-                let usecase = Arc::clone(&block_usecase);
-                move |path| handler_get_block_by_hash(path, usecase)
-            }),
-        )
-        .route(
-            "/blocks/verify",
-            get({
-                let usecase = Arc::clone(&block_usecase);
-                move || handler_verify_chain(usecase)
-            }),
+async fn migrate(setting: Arc<Setting>, dry_run: bool) -> Result<(), String> {
+    if setting.storage.backend != StorageBackend::Mongo {
+        return Err("migrations only apply to the mongo backend".to_string());
+    }
+    let db = database::db_connect(setting)
+        .await
+        .map_err(|e| e.to_string())?;
+    let reports = migrations::migrate(&db, dry_run, TimerHelper::Directly.creation()).await?;
+    if reports.is_empty() {
+        println!("no pending migrations");
+    }
+    for report in reports {
+        let verb = if dry_run { "would rewrite" } else { "rewrote" };
+        println!(
+            "{} {}: {} {} documents",
+            report.version, report.name, verb, report.documents
         );
+    }
+    return Ok(());
 }
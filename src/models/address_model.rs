@@ -1,14 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-
+/// Registers `public_key`, `signature` proves the caller holds its secret
+/// key, see `crypto_helper::verify_registration`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InsertAddress {
-    pub public_key: String
+    pub public_key: String,
+    pub signature: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct CoinWithAddress {
-    pub public_key: String,
+    pub address: String,
     pub amount: u64,
 }
-
-
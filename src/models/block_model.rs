@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum ChainViolation {
+    IndexGap { expected: u64 },
+    DuplicateIndex,
+    PreviousHashMismatch { expected: String },
+    MalformedHeader { error: String },
+    DuplicateTransaction { position: u64 },
+    TransactionNotFound { position: u64, tx_id: String },
+    TransactionHashMismatch { position: u64, expected: String },
+    MissingCoinbase,
+    ExtraCoinbase { position: u64 },
+    CoinbaseRewardMismatch { expected: u64 },
+    CoinbaseFeeMismatch { expected: u64 },
+    FeeOverflow,
+    MerkleRootMismatch { expected: String },
+    HashMismatch { expected: String },
+    TimestampNotMonotonic { previous: i64 },
+    DifficultyMismatch { expected: u32 },
+    InsufficientWork,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvalidBlock {
+    pub index: u64,
+    pub hash: String,
+    #[serde(flatten)]
+    pub violation: ChainViolation,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainReport {
+    pub valid: bool,
+    pub checked_blocks: u64,
+    pub first_invalid: Option<InvalidBlock>,
+}
+
+/// Merkle branch proving that a transaction hash is committed to by the
+/// `merkle_root` of the block header at `block_index`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionProof {
+    pub tx_id: String,
+    pub tx_hash: String,
+    pub block_hash: String,
+    pub block_index: u64,
+    pub merkle_root: String,
+    pub position: u64,
+    pub branch: Vec<String>,
+}
+
+/// A transaction of a block whose signature did not verify.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignatureFailure {
+    pub tx_id: Option<String>,
+    pub tx_hash: String,
+    pub error: String,
+}
+
+/// Outcome of re-verifying every signature of a block. `checked` counts
+/// the signed transactions found, not the unsigned coinbase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignatureReport {
+    pub block_hash: String,
+    pub checked: u64,
+    pub failed: Vec<SignatureFailure>,
+}
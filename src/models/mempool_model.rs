@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MempoolStats {
+    pub transactions: u64,
+    pub bytes: u64,
+    pub total_fees: u64,
+    pub min_fee_rate: f64,
+    pub max_fee_rate: f64,
+    pub max_transactions: u64,
+}
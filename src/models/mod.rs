@@ -1,2 +1,4 @@
 pub mod address_model;
-pub mod transaction_model;
\ No newline at end of file
+pub mod transaction_model;
+pub mod block_model;
+pub mod mempool_model;
\ No newline at end of file
@@ -1,9 +1,40 @@
 use serde::{Deserialize, Serialize};
 
+/// How a transaction is signed. ECDSA signatures are hex DER, Schnorr
+/// signatures are hex 64 byte BIP340 signatures checked against the x-only
+/// part of the sender's public key.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureScheme {
+    #[default]
+    Ecdsa,
+    Schnorr,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateTransactionRequest {
     pub from: String,
     pub to: String,
     pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    #[serde(default)]
+    pub valid_until: Option<i64>,
+    #[serde(default)]
+    pub scheme: SignatureScheme,
+    pub public_key: String,
     pub signature: String,
-}
\ No newline at end of file
+}
+
+/// The fields a sender signs, see `crypto_helper::payload_bytes`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionPayload {
+    pub chain_id: u64,
+    pub from: String,
+    pub to: String,
+    pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    pub valid_until: Option<i64>,
+    pub scheme: SignatureScheme,
+}
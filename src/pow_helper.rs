@@ -0,0 +1,101 @@
+use crate::{
+    entities::block_entity::BlockEntity,
+    header_helper::{BlockHeader, LEGACY_HEADER_VERSION},
+    setting::Mining,
+};
+
+/// A SHA-256 hash has 256 bits, no hash meets a higher difficulty.
+pub const MAX_DIFFICULTY: u32 = 256;
+
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+            continue;
+        }
+        bits += byte.leading_zeros();
+        break;
+    }
+    return bits;
+}
+
+/// Checks that a hex encoded hash has at least `difficulty` leading zero bits.
+pub fn meets_difficulty(hash: &str, difficulty: u32) -> bool {
+    return match hex::decode(hash) {
+        Ok(bytes) => leading_zero_bits(&bytes) >= difficulty,
+        Err(_) => false,
+    };
+}
+
+/// Searches nonces from zero until the block hash meets its difficulty and
+/// stores the nonce and hash in the block, or fails once every nonce was
+/// tried.
+/// This is CPU bound, call it from a blocking thread.
+pub fn mine(block: &mut BlockEntity) -> Result<(), String> {
+    let mut header = BlockHeader::from_block(block)?;
+    if header.difficulty > MAX_DIFFICULTY {
+        return Err(format!(
+            "difficulty {} is above the maximum {}",
+            header.difficulty, MAX_DIFFICULTY
+        ));
+    }
+    header.nonce = 0;
+    loop {
+        let hash = header.hash();
+        if leading_zero_bits(&hash) >= header.difficulty {
+            block.nonce = header.nonce;
+            block.hash = hex::encode(hash);
+            return Ok(());
+        }
+        header.nonce = match header.nonce.checked_add(1) {
+            Some(nonce) => nonce,
+            None => {
+                return Err(format!("no nonce meets difficulty {}", header.difficulty));
+            }
+        };
+    }
+}
+
+/// Returns the difficulty the block following `chain` must declare.
+///
+/// `chain` is ordered by index and must hold at least the last
+/// `retarget_interval` blocks. Every `retarget_interval` blocks the time the
+/// window took is compared with `target_block_time`; the timespan is clamped
+/// by `max_adjustment_factor` and difficulty moves by whole bits, so it can
+/// change by at most `log2(max_adjustment_factor)` bits per window, and
+/// stays between 1 and `MAX_DIFFICULTY`. Legacy blocks declare no
+/// difficulty, the block after them starts over at `mining.difficulty`.
+pub fn next_difficulty(chain: &[BlockEntity], mining: &Mining) -> u32 {
+    let last = match chain.last() {
+        Some(block) if block.version != LEGACY_HEADER_VERSION => block,
+        _ => return mining.difficulty,
+    };
+
+    let interval = mining.retarget_interval;
+    if interval < 2 || last.index % interval != 0 || (chain.len() as u64) < interval {
+        return last.difficulty;
+    }
+
+    let first = &chain[chain.len() - interval as usize];
+    let factor = mining.max_adjustment_factor.max(1) as i64;
+    let expected = (mining.target_block_time * (interval - 1)) as i64;
+    let actual = (last.timestamp - first.timestamp).clamp(expected / factor, expected * factor);
+
+    let mut difficulty = last.difficulty;
+    if actual < expected {
+        let mut span = actual.max(1);
+        while span * 2 <= expected {
+            span *= 2;
+            difficulty += 1;
+        }
+    } else {
+        let mut span = expected.max(1);
+        while span * 2 <= actual {
+            span *= 2;
+            difficulty = difficulty.saturating_sub(1);
+        }
+    }
+
+    return difficulty.clamp(1, MAX_DIFFICULTY);
+}
@@ -0,0 +1,156 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicI64, Ordering},
+    };
+
+    use crate::{
+        entities::block_entity::BlockEntity,
+        header_helper::{LEGACY_HEADER_VERSION, calculate_hash},
+        pow_helper::{MAX_DIFFICULTY, leading_zero_bits, meets_difficulty, mine, next_difficulty},
+        setting::{Mining, Setting},
+        timer_helper::{MockIntoTimerHelper, TimerHelper},
+    };
+
+    fn mining() -> Mining {
+        let setting = Setting::from_toml(include_str!("../Settings.toml")).unwrap();
+        return Mining {
+            difficulty: 16,
+            retarget_interval: 10,
+            target_block_time: 60,
+            max_adjustment_factor: 4,
+            ..setting.mining.clone()
+        };
+    }
+
+    fn chain(count: u64, block_time: i64, difficulty: u32) -> Vec<BlockEntity> {
+        let clock = Arc::new(AtomicI64::new(0));
+        let mut timer_helper = MockIntoTimerHelper::new();
+        timer_helper
+            .expect_now()
+            .returning(move || clock.fetch_add(block_time, Ordering::SeqCst));
+        let timer_helper = Arc::new(timer_helper);
+
+        return (1..=count)
+            .map(|index| {
+                BlockEntity::new(
+                    index,
+                    vec![],
+                    vec![],
+                    String::new(),
+                    String::new(),
+                    difficulty,
+                    timer_helper.clone(),
+                )
+            })
+            .collect();
+    }
+
+    #[test]
+    fn leading_zero_bits_test() {
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0xff]), 16);
+        assert_eq!(leading_zero_bits(&[0x00, 0x0f, 0x00]), 12);
+        assert_eq!(leading_zero_bits(&[0x80]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn mine_test() {
+        let mut block = BlockEntity::new(
+            1,
+            vec![],
+            vec![],
+            String::new(),
+            String::new(),
+            8,
+            TimerHelper::Mock.creation(),
+        );
+        mine(&mut block).unwrap();
+
+        assert!(meets_difficulty(&block.hash, 8));
+        assert_eq!(calculate_hash(&block).unwrap(), block.hash);
+    }
+
+    #[test]
+    fn mine_refuses_unreachable_difficulty_test() {
+        let mut block = BlockEntity::new(
+            1,
+            vec![],
+            vec![],
+            String::new(),
+            String::new(),
+            MAX_DIFFICULTY + 1,
+            TimerHelper::Mock.creation(),
+        );
+
+        assert!(mine(&mut block).is_err());
+    }
+
+    #[test]
+    fn difficulty_setting_range_test() {
+        let toml = include_str!("../Settings.toml");
+
+        for difficulty in ["0", "257"] {
+            let toml = toml.replace("difficulty = 16", &format!("difficulty = {}", difficulty));
+            assert!(Setting::from_toml(&toml).is_err());
+        }
+        let toml = toml.replace("difficulty = 16", "difficulty = 256");
+        assert_eq!(Setting::from_toml(&toml).unwrap().mining.difficulty, 256);
+    }
+
+    #[test]
+    fn meets_difficulty_rejects_invalid_hex_test() {
+        assert!(!meets_difficulty("not a hash", 0));
+    }
+
+    #[test]
+    fn next_difficulty_genesis_test() {
+        assert_eq!(next_difficulty(&[], &mining()), 16);
+    }
+
+    #[test]
+    fn next_difficulty_keeps_inside_window_test() {
+        assert_eq!(next_difficulty(&chain(5, 1, 16), &mining()), 16);
+    }
+
+    #[test]
+    fn next_difficulty_on_target_test() {
+        assert_eq!(next_difficulty(&chain(10, 60, 16), &mining()), 16);
+    }
+
+    #[test]
+    fn next_difficulty_fast_blocks_test() {
+        assert_eq!(next_difficulty(&chain(10, 30, 16), &mining()), 17);
+    }
+
+    #[test]
+    fn next_difficulty_slow_blocks_test() {
+        assert_eq!(next_difficulty(&chain(10, 120, 16), &mining()), 15);
+    }
+
+    #[test]
+    fn next_difficulty_is_clamped_test() {
+        assert_eq!(next_difficulty(&chain(10, 0, 16), &mining()), 18);
+        assert_eq!(next_difficulty(&chain(10, 6000, 16), &mining()), 14);
+    }
+
+    #[test]
+    fn next_difficulty_stays_in_range_test() {
+        assert_eq!(
+            next_difficulty(&chain(10, 0, MAX_DIFFICULTY - 1), &mining()),
+            MAX_DIFFICULTY
+        );
+        assert_eq!(next_difficulty(&chain(10, 6000, 1), &mining()), 1);
+    }
+
+    #[test]
+    fn next_difficulty_after_legacy_blocks_test() {
+        let mut chain = chain(5, 60, 0);
+        for block in &mut chain {
+            block.version = LEGACY_HEADER_VERSION;
+        }
+
+        assert_eq!(next_difficulty(&chain, &mining()), 16);
+    }
+}
@@ -4,11 +4,49 @@ use crate::entities::address_entity::AddressEntity;
 use async_trait::async_trait;
 use bson::{Document, doc, from_document, oid::ObjectId};
 use mockall::automock;
-use mongodb::Database;
+use mongodb::{
+    ClientSession, Database,
+    error::{ErrorKind, WriteFailure},
+};
+use redb::WriteTransaction;
 use tracing::error;
 
+use super::{
+    memory_store::{MemoryState, MemoryStore, ensure_unique_address, insert_document},
+    redb_store::{self, ADDRESSES_BY_ADDRESS, RedbRead, RedbStore, replace_document},
+    session::{BoxedSession, mongo_session},
+};
+
 pub type SharedAddressRepository = Arc<dyn AddressRepository + Send + Sync>;
 
+/// Error of a deposit past the largest balance Mongo stores, an i64, on
+/// every backend.
+pub const BALANCE_OVERFLOW: &str = "balance would overflow";
+
+/// Applies `amount` to `balance` like a Mongo `$inc` on the stored i64,
+/// which fails rather than wrap.
+fn add_to_balance(balance: u64, amount: i64) -> Result<u64, String> {
+    return match balance.checked_add_signed(amount) {
+        Some(balance) if balance <= i64::MAX as u64 => Ok(balance),
+        _ => {
+            error!("update balance: {} + {} overflows", balance, amount);
+            Err(BALANCE_OVERFLOW.to_string())
+        }
+    };
+}
+
+/// `amount` as a change of the i64 balance, refused when it doesn't fit
+/// rather than wrap to the opposite sign.
+fn balance_change(amount: u64) -> Result<i64, String> {
+    return match i64::try_from(amount) {
+        Ok(amount) => Ok(amount),
+        Err(_) => {
+            error!("update balance: amount {} is out of range", amount);
+            Err(format!("amount {} is out of range", amount))
+        }
+    };
+}
+
 #[async_trait]
 #[automock]
 pub trait AddressRepository {
@@ -17,6 +55,23 @@ pub trait AddressRepository {
     async fn insert(&self, insert_address: AddressEntity) -> Result<ObjectId, String>;
     async fn deposit(&self, address: AddressEntity, amount: u64) -> Result<(), String>;
     async fn withdraw(&self, address: AddressEntity, amount: u64) -> Result<(), String>;
+    async fn deposit_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String>;
+    async fn withdraw_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String>;
+    async fn increment_nonce_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+    ) -> Result<(), String>;
 }
 
 pub struct MongoAddressRepository {
@@ -27,6 +82,67 @@ impl MongoAddressRepository {
     pub fn creation(db: Database) -> SharedAddressRepository {
         return Arc::new(Self { db });
     }
+
+    /// Adds `amount` to the balance. A negative `amount` only matches when
+    /// the balance covers it, so a withdrawal never leaves it below zero.
+    async fn update_balance(
+        &self,
+        session: Option<&mut ClientSession>,
+        address: AddressEntity,
+        amount: i64,
+    ) -> Result<(), String> {
+        let mut filter = doc! {
+            "address": &address.address
+        };
+        if amount < 0 {
+            filter.insert("balance", doc! { "$gte": -amount });
+        }
+        let update = doc! {
+            "$inc": { "balance": amount },
+            "$set": { "updated_at": address.updated_at }
+        };
+
+        let collection = self.db.collection::<Document>("addresses");
+        let action = collection.update_one(filter, update);
+        let result = match session {
+            Some(session) => action.session(session).await,
+            None => action.await,
+        };
+
+        return match result {
+            Ok(update_result) => {
+                if update_result.matched_count == 0 && amount < 0 {
+                    error!(
+                        "update balance: cannot withdraw {} from {}",
+                        -amount, address.address
+                    );
+                    return Err("address not found or balance is not enough".to_string());
+                }
+                if update_result.matched_count == 0 {
+                    error!("update balance: address not found: {}", address.address);
+                    return Err("address not found".to_string());
+                }
+
+                Ok(())
+            }
+            // the server refuses an $inc past the i64 range with BadValue
+            Err(e) if is_write_error(&e, 2) => {
+                error!("update balance: {} + {} overflows", address.address, amount);
+                Err(BALANCE_OVERFLOW.to_string())
+            }
+            Err(e) => {
+                error!("update balance error: {}", e);
+                Err(e.to_string())
+            }
+        };
+    }
+}
+
+fn is_write_error(error: &mongodb::error::Error, code: i32) -> bool {
+    return matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == code
+    );
 }
 
 #[async_trait]
@@ -68,7 +184,7 @@ impl AddressRepository for MongoAddressRepository {
             .db
             .collection::<Document>("addresses")
             .find_one(doc! {
-                "public_key": address,
+                "address": address,
             })
             .await;
 
@@ -100,8 +216,10 @@ impl AddressRepository for MongoAddressRepository {
             .db
             .collection::<Document>("addresses")
             .insert_one(doc! {
+                "address": new_address.address,
                 "public_key": new_address.public_key,
                 "balance": new_address.balance as i64,
+                "nonce": new_address.nonce as i64,
                 "created_at": new_address.created_at,
                 "updated_at": new_address.updated_at,
             })
@@ -127,11 +245,52 @@ impl AddressRepository for MongoAddressRepository {
     }
 
     async fn deposit(&self, address: AddressEntity, amount: u64) -> Result<(), String> {
+        return self
+            .update_balance(None, address, balance_change(amount)?)
+            .await;
+    }
+
+    async fn withdraw(&self, address: AddressEntity, amount: u64) -> Result<(), String> {
+        return self
+            .update_balance(None, address, -balance_change(amount)?)
+            .await;
+    }
+
+    async fn deposit_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String> {
+        let session = mongo_session(session)?;
+        return self
+            .update_balance(Some(session), address, balance_change(amount)?)
+            .await;
+    }
+
+    async fn withdraw_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String> {
+        let session = mongo_session(session)?;
+        return self
+            .update_balance(Some(session), address, -balance_change(amount)?)
+            .await;
+    }
+
+    async fn increment_nonce_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+    ) -> Result<(), String> {
+        let session = mongo_session(session)?;
         let filter = doc! {
-            "public_key": &address.public_key
+            "address": &address.address
         };
         let update = doc! {
-            "$inc": { "balance": amount as i64 },
+            "$inc": { "nonce": 1_i64 },
             "$set": { "updated_at": address.updated_at }
         };
 
@@ -139,52 +298,258 @@ impl AddressRepository for MongoAddressRepository {
             .db
             .collection::<Document>("addresses")
             .update_one(filter, update)
+            .session(session)
             .await;
 
         return match result {
             Ok(update_result) => {
                 if update_result.matched_count == 0 {
-                    error!("deposit: address not found: {}", address.public_key);
+                    error!("increment nonce: address not found: {}", address.address);
                     return Err("address not found".to_string());
                 }
 
                 Ok(())
             }
             Err(e) => {
-                error!("deposit error: {}", e);
+                error!("increment nonce error: {}", e);
                 Err(e.to_string())
             }
         };
     }
+}
 
-    async fn withdraw(&self, address: AddressEntity, amount: u64) -> Result<(), String> {
-        let filter = doc! {
-            "public_key": &address.public_key,
+pub struct MemoryAddressRepository {
+    store: Arc<MemoryStore>,
+}
+
+impl MemoryAddressRepository {
+    pub fn creation(store: Arc<MemoryStore>) -> SharedAddressRepository {
+        return Arc::new(Self { store });
+    }
+
+    fn find<'a>(state: &'a mut MemoryState, address: &str) -> Option<&'a mut AddressEntity> {
+        return state.addresses.values_mut().find(|a| a.address == address);
+    }
+
+    /// Same rules as `MongoAddressRepository::update_balance`.
+    fn update_balance(
+        state: &mut MemoryState,
+        address: AddressEntity,
+        amount: i64,
+    ) -> Result<(), String> {
+        let found = match Self::find(state, &address.address) {
+            Some(found) if amount >= 0 || found.balance >= amount.unsigned_abs() => found,
+            _ if amount < 0 => {
+                error!(
+                    "update balance: cannot withdraw {} from {}",
+                    -amount, address.address
+                );
+                return Err("address not found or balance is not enough".to_string());
+            }
+            _ => {
+                error!("update balance: address not found: {}", address.address);
+                return Err("address not found".to_string());
+            }
         };
-        let update = doc! {
-            "$inc": { "balance": -1 * (amount as i64)},
-            "$set": { "updated_at": address.updated_at }
+
+        found.balance = add_to_balance(found.balance, amount)?;
+        found.updated_at = address.updated_at;
+        return Ok(());
+    }
+
+    fn increment_nonce(state: &mut MemoryState, address: AddressEntity) -> Result<(), String> {
+        let Some(found) = Self::find(state, &address.address) else {
+            error!("increment nonce: address not found: {}", address.address);
+            return Err("address not found".to_string());
         };
 
-        let result = self
-            .db
-            .collection::<Document>("addresses")
-            .update_one(filter, update)
-            .await;
+        found.nonce += 1;
+        found.updated_at = address.updated_at;
+        return Ok(());
+    }
+}
 
-        return match result {
-            Ok(update_result) => {
-                if update_result.matched_count == 0 {
-                    error!("withdraw: address not found: {}", address.public_key);
-                    return Err("withdraw not found".to_string());
-                }
+#[async_trait]
+impl AddressRepository for MemoryAddressRepository {
+    async fn get_by_id(&self, id: ObjectId) -> Result<Option<AddressEntity>, String> {
+        return Ok(self.store.read(|state| state.addresses.get(&id).cloned()));
+    }
 
-                Ok(())
+    async fn get_by_address(&self, address: String) -> Result<Option<AddressEntity>, String> {
+        return Ok(self.store.read(|state| {
+            state
+                .addresses
+                .values()
+                .find(|a| a.address == address)
+                .cloned()
+        }));
+    }
+
+    async fn insert(&self, mut new_address: AddressEntity) -> Result<ObjectId, String> {
+        // like the Mongo insert, the document always gets a fresh _id
+        let id = ObjectId::new();
+        new_address.id = Some(id);
+        return self.store.write(|state| {
+            ensure_unique_address(&state.addresses, id, &new_address)?;
+            return insert_document(&mut state.addresses, id, new_address);
+        });
+    }
+
+    async fn deposit(&self, address: AddressEntity, amount: u64) -> Result<(), String> {
+        return self
+            .store
+            .write(|state| Self::update_balance(state, address, balance_change(amount)?));
+    }
+
+    async fn withdraw(&self, address: AddressEntity, amount: u64) -> Result<(), String> {
+        return self
+            .store
+            .write(|state| Self::update_balance(state, address, -balance_change(amount)?));
+    }
+
+    async fn deposit_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String> {
+        let state = self.store.session_state(session)?;
+        return Self::update_balance(state, address, balance_change(amount)?);
+    }
+
+    async fn withdraw_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String> {
+        let state = self.store.session_state(session)?;
+        return Self::update_balance(state, address, -balance_change(amount)?);
+    }
+
+    async fn increment_nonce_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+    ) -> Result<(), String> {
+        let state = self.store.session_state(session)?;
+        return Self::increment_nonce(state, address);
+    }
+}
+
+pub struct RedbAddressRepository {
+    store: Arc<RedbStore>,
+}
+
+impl RedbAddressRepository {
+    pub fn creation(store: Arc<RedbStore>) -> SharedAddressRepository {
+        return Arc::new(Self { store });
+    }
+
+    fn find(txn: &impl RedbRead, address: &str) -> Result<Option<AddressEntity>, String> {
+        return Ok(txn
+            .find_by(ADDRESSES_BY_ADDRESS, address.as_bytes())?
+            .into_iter()
+            .next());
+    }
+
+    /// Same rules as `MongoAddressRepository::update_balance`.
+    fn update_balance(
+        txn: &WriteTransaction,
+        address: AddressEntity,
+        amount: i64,
+    ) -> Result<(), String> {
+        let mut found = match Self::find(txn, &address.address)? {
+            Some(found) if amount >= 0 || found.balance >= amount.unsigned_abs() => found,
+            _ if amount < 0 => {
+                error!(
+                    "update balance: cannot withdraw {} from {}",
+                    -amount, address.address
+                );
+                return Err("address not found or balance is not enough".to_string());
             }
-            Err(e) => {
-                error!("withdraw error: {}", e);
-                Err(e.to_string())
+            _ => {
+                error!("update balance: address not found: {}", address.address);
+                return Err("address not found".to_string());
             }
         };
+
+        found.balance = add_to_balance(found.balance, amount)?;
+        found.updated_at = address.updated_at;
+        return replace_document(txn, &found);
+    }
+
+    fn increment_nonce(txn: &WriteTransaction, address: AddressEntity) -> Result<(), String> {
+        let Some(mut found) = Self::find(txn, &address.address)? else {
+            error!("increment nonce: address not found: {}", address.address);
+            return Err("address not found".to_string());
+        };
+
+        found.nonce += 1;
+        found.updated_at = address.updated_at;
+        return replace_document(txn, &found);
+    }
+}
+
+#[async_trait]
+impl AddressRepository for RedbAddressRepository {
+    async fn get_by_id(&self, id: ObjectId) -> Result<Option<AddressEntity>, String> {
+        return self.store.read(move |txn| txn.document(&id)).await;
+    }
+
+    async fn get_by_address(&self, address: String) -> Result<Option<AddressEntity>, String> {
+        return self.store.read(move |txn| Self::find(txn, &address)).await;
+    }
+
+    async fn insert(&self, mut new_address: AddressEntity) -> Result<ObjectId, String> {
+        // like the Mongo insert, the document always gets a fresh _id
+        new_address.id = Some(ObjectId::new());
+        return self
+            .store
+            .write(move |txn| redb_store::insert_document(txn, &new_address))
+            .await;
+    }
+
+    async fn deposit(&self, address: AddressEntity, amount: u64) -> Result<(), String> {
+        return self
+            .store
+            .write(move |txn| Self::update_balance(txn, address, balance_change(amount)?))
+            .await;
+    }
+
+    async fn withdraw(&self, address: AddressEntity, amount: u64) -> Result<(), String> {
+        return self
+            .store
+            .write(move |txn| Self::update_balance(txn, address, -balance_change(amount)?))
+            .await;
+    }
+
+    async fn deposit_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String> {
+        let txn = self.store.session_txn(session)?;
+        return Self::update_balance(txn, address, balance_change(amount)?);
+    }
+
+    async fn withdraw_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+        amount: u64,
+    ) -> Result<(), String> {
+        let txn = self.store.session_txn(session)?;
+        return Self::update_balance(txn, address, -balance_change(amount)?);
+    }
+
+    async fn increment_nonce_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: AddressEntity,
+    ) -> Result<(), String> {
+        let txn = self.store.session_txn(session)?;
+        return Self::increment_nonce(txn, address);
     }
 }
@@ -1,11 +1,23 @@
 use async_trait::async_trait;
 use bson::{Document, doc, from_document, oid::ObjectId};
 use mockall::automock;
-use mongodb::Database;
-use std::sync::Arc;
+use mongodb::{ClientSession, Database};
+use redb::WriteTransaction;
+use std::{collections::HashMap, sync::Arc};
 use tracing::error;
 
-use crate::entities::block_entity::BlockEntity;
+use super::{
+    memory_store::{MemoryState, MemoryStore, ensure_unique_block, insert_document},
+    redb_store::{self, BLOCKS_BY_HASH, BLOCKS_BY_INDEX, RedbRead, RedbStore},
+    session::{BoxedSession, mongo_session},
+};
+use crate::{
+    chain_helper,
+    database::migrations,
+    entities::{block_entity::BlockEntity, transaction_entity::TransactionEntity},
+    models::block_model::ChainReport,
+    setting::Setting,
+};
 
 pub type SharedBlockRepository = Arc<dyn BlockRepository + Send + Sync>;
 
@@ -14,20 +26,93 @@ pub type SharedBlockRepository = Arc<dyn BlockRepository + Send + Sync>;
 pub trait BlockRepository {
     async fn find_latest(&self) -> Result<Option<BlockEntity>, String>;
     async fn find_by_hash(&self, hash: String) -> Result<Option<BlockEntity>, String>;
+    async fn find_recent(&self, limit: u64) -> Result<Vec<BlockEntity>, String>;
 
     async fn insert(&self, block: BlockEntity) -> Result<ObjectId, String>;
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        block: BlockEntity,
+    ) -> Result<ObjectId, String>;
 
-    async fn is_chain_valid(&self) -> Result<(), String>;
+    async fn is_chain_valid(&self) -> Result<ChainReport, String>;
     async fn get_last_index(&self) -> Result<u64, String>;
 }
 
 pub struct MongoBlockRepository {
     db: Database,
+    setting: Arc<Setting>,
 }
 
 impl MongoBlockRepository {
-    pub fn creation(db: Database) -> SharedBlockRepository {
-        return Arc::new(Self { db });
+    pub fn creation(db: Database, setting: Arc<Setting>) -> SharedBlockRepository {
+        return Arc::new(Self { db, setting });
+    }
+
+    async fn insert_one(
+        &self,
+        session: Option<&mut ClientSession>,
+        block: BlockEntity,
+    ) -> Result<ObjectId, String> {
+        let collection = self.db.collection::<Document>("blocks");
+        let action = collection.insert_one(doc! {
+            "version": block.version as i64,
+            "index": block.index as i64,
+            "timestamp": block.timestamp,
+            "transactions": block.transactions,
+            "transaction_hashes": block.transaction_hashes,
+            "previous_hash": block.previous_hash,
+            "merkle_root": block.merkle_root,
+            "hash": block.hash,
+            "nonce": block.nonce as i64,
+            "difficulty": block.difficulty as i64,
+        });
+        let inserted_object_id = match session {
+            Some(session) => action.session(session).await,
+            None => action.await,
+        }
+        .map_err(|e| {
+            error!("insert a new block failed: {}", e);
+            return e.to_string();
+        })?
+        .inserted_id
+        .as_object_id();
+
+        return match inserted_object_id {
+            Some(id) => Ok(id),
+            None => {
+                error!("issue with new _id");
+                return Err(String::new());
+            }
+        };
+    }
+
+    /// The stored transactions `block` lists, by `_id`. Queried one block
+    /// at a time, the id list fits a query as it fits the block document.
+    async fn block_transactions(
+        &self,
+        block: &BlockEntity,
+    ) -> Result<HashMap<ObjectId, TransactionEntity>, String> {
+        let mut cursor = self
+            .db
+            .collection::<Document>("transactions")
+            .find(doc! { "_id": { "$in": &block.transactions } })
+            .await
+            .map_err(|e| {
+                error!("is_chain_valid: failed to query transactions: {}", e);
+                e.to_string()
+            })?;
+
+        let mut transactions = HashMap::new();
+        while cursor.advance().await.map_err(|e| e.to_string())? {
+            let doc = cursor.deserialize_current().map_err(|e| e.to_string())?;
+            let tx: TransactionEntity = from_document(doc).map_err(|e| e.to_string())?;
+            if let Some(id) = tx.id {
+                transactions.insert(id, tx);
+            }
+        }
+
+        return Ok(transactions);
     }
 }
 
@@ -90,33 +175,44 @@ impl BlockRepository for MongoBlockRepository {
         return Ok(Some(block));
     }
 
-    async fn insert(&self, block: BlockEntity) -> Result<ObjectId, String> {
-        let inserted_object_id = self
+    async fn find_recent(&self, limit: u64) -> Result<Vec<BlockEntity>, String> {
+        let mut cursor = self
             .db
             .collection::<Document>("blocks")
-            .insert_one(doc! {
-                "index": block.index as i64,
-                "timestamp": block.timestamp,
-                "transactions": block.transactions,
-                "previous_hash": block.previous_hash,
-                "hash": block.hash,
-                "nonce": block.nonce as i64
-            })
+            .find(doc! {})
+            .sort(doc! { "index": -1 })
+            .limit(limit as i64)
             .await
             .map_err(|e| {
-                error!("insert a new block failed: {}", e);
-                return e.to_string();
-            })?
-            .inserted_id
-            .as_object_id();
+                error!("find recent blocks error: {}", e);
+                e.to_string()
+            })?;
 
-        return match inserted_object_id {
-            Some(id) => Ok(id),
-            None => {
-                error!("issue with new _id");
-                return Err(String::new());
-            }
-        };
+        let mut blocks: Vec<BlockEntity> = Vec::new();
+        while cursor.advance().await.map_err(|e| e.to_string())? {
+            let doc = cursor.deserialize_current().map_err(|e| e.to_string())?;
+            let block: BlockEntity = from_document(doc).map_err(|e| {
+                error!("convert doc to BlockEntity failed: {}", e);
+                e.to_string()
+            })?;
+            blocks.push(block);
+        }
+
+        blocks.reverse();
+        return Ok(blocks);
+    }
+
+    async fn insert(&self, block: BlockEntity) -> Result<ObjectId, String> {
+        return self.insert_one(None, block).await;
+    }
+
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        block: BlockEntity,
+    ) -> Result<ObjectId, String> {
+        let session = mongo_session(session)?;
+        return self.insert_one(Some(session), block).await;
     }
 
     async fn get_last_index(&self) -> Result<u64, String> {
@@ -142,7 +238,7 @@ impl BlockRepository for MongoBlockRepository {
         }
     }
 
-    async fn is_chain_valid(&self) -> Result<(), String> {
+    async fn is_chain_valid(&self) -> Result<ChainReport, String> {
         let mut cursor = self
             .db
             .collection::<Document>("blocks")
@@ -162,16 +258,214 @@ impl BlockRepository for MongoBlockRepository {
 
         blocks.sort_by_key(|b| b.index);
 
-        for i in 1..blocks.len() {
-            if blocks[i].previous_hash != blocks[i - 1].hash {
-                error!(
-                    "Invalid chain at block index {}: previous_hash mismatch",
-                    blocks[i].index
-                );
-                return Err(format!("Chain broken at block index {}", blocks[i].index));
+        let legacy_height = migrations::legacy_height(&self.db).await?;
+
+        let mut report = chain_helper::valid_chain(blocks.len() as u64);
+        for (i, block) in blocks.iter().enumerate() {
+            let transactions = self.block_transactions(block).await?;
+            if let Some(violation) = chain_helper::check_block(
+                &blocks[..i],
+                block,
+                &transactions,
+                legacy_height,
+                &self.setting.mining,
+            ) {
+                report = chain_helper::invalid_chain(i as u64 + 1, block, violation);
+                break;
             }
         }
+        if let Some(invalid) = &report.first_invalid {
+            error!(
+                "Invalid chain at block index {}: {:?}",
+                invalid.index, invalid.violation
+            );
+        }
+
+        return Ok(report);
+    }
+}
+
+pub struct MemoryBlockRepository {
+    store: Arc<MemoryStore>,
+    setting: Arc<Setting>,
+}
+
+impl MemoryBlockRepository {
+    pub fn creation(store: Arc<MemoryStore>, setting: Arc<Setting>) -> SharedBlockRepository {
+        return Arc::new(Self { store, setting });
+    }
+
+    /// Every block ordered by index.
+    fn sorted_blocks(&self) -> Vec<BlockEntity> {
+        let mut blocks: Vec<BlockEntity> = self
+            .store
+            .read(|state| state.blocks.values().cloned().collect());
+        blocks.sort_by_key(|b| b.index);
+        return blocks;
+    }
+
+    fn insert_one(state: &mut MemoryState, mut block: BlockEntity) -> Result<ObjectId, String> {
+        // like the Mongo insert, the document always gets a fresh _id
+        let id = ObjectId::new();
+        block.id = Some(id);
+        ensure_unique_block(&state.blocks, id, &block)?;
+        return insert_document(&mut state.blocks, id, block);
+    }
+}
+
+#[async_trait]
+impl BlockRepository for MemoryBlockRepository {
+    async fn find_latest(&self) -> Result<Option<BlockEntity>, String> {
+        return Ok(self.sorted_blocks().pop());
+    }
+
+    async fn find_by_hash(&self, hash: String) -> Result<Option<BlockEntity>, String> {
+        return Ok(self
+            .store
+            .read(|state| state.blocks.values().find(|b| b.hash == hash).cloned()));
+    }
+
+    async fn find_recent(&self, limit: u64) -> Result<Vec<BlockEntity>, String> {
+        let mut blocks = self.sorted_blocks();
+        let skip = blocks.len().saturating_sub(limit as usize);
+        return Ok(blocks.split_off(skip));
+    }
+
+    async fn insert(&self, block: BlockEntity) -> Result<ObjectId, String> {
+        return self.store.write(|state| Self::insert_one(state, block));
+    }
+
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        block: BlockEntity,
+    ) -> Result<ObjectId, String> {
+        let state = self.store.session_state(session)?;
+        return Self::insert_one(state, block);
+    }
+
+    async fn is_chain_valid(&self) -> Result<ChainReport, String> {
+        let blocks = self.sorted_blocks();
+        let transactions: HashMap<ObjectId, TransactionEntity> = self.store.read(|state| {
+            return blocks
+                .iter()
+                .flat_map(|b| b.transactions.iter())
+                .filter_map(|id| Some((*id, state.transactions.get(id)?.clone())))
+                .collect();
+        });
+
+        // only Mongo nodes ran the migration that leaves legacy blocks
+        let report = chain_helper::validate_chain(&blocks, &transactions, 0, &self.setting.mining);
+        if let Some(invalid) = &report.first_invalid {
+            error!(
+                "Invalid chain at block index {}: {:?}",
+                invalid.index, invalid.violation
+            );
+        }
+
+        return Ok(report);
+    }
+
+    async fn get_last_index(&self) -> Result<u64, String> {
+        return Ok(self.sorted_blocks().last().map(|b| b.index).unwrap_or(0));
+    }
+}
+
+pub struct RedbBlockRepository {
+    store: Arc<RedbStore>,
+    setting: Arc<Setting>,
+}
+
+impl RedbBlockRepository {
+    pub fn creation(store: Arc<RedbStore>, setting: Arc<Setting>) -> SharedBlockRepository {
+        return Arc::new(Self { store, setting });
+    }
+
+    /// The last `limit` blocks ordered by index.
+    async fn last_blocks(&self, limit: usize) -> Result<Vec<BlockEntity>, String> {
+        return self
+            .store
+            .read(move |txn| txn.documents(txn.index_tail(BLOCKS_BY_INDEX, limit)?))
+            .await;
+    }
+
+    fn insert_one(txn: &WriteTransaction, mut block: BlockEntity) -> Result<ObjectId, String> {
+        // like the Mongo insert, the document always gets a fresh _id
+        block.id = Some(ObjectId::new());
+        return redb_store::insert_document(txn, &block);
+    }
+}
+
+#[async_trait]
+impl BlockRepository for RedbBlockRepository {
+    async fn find_latest(&self) -> Result<Option<BlockEntity>, String> {
+        return Ok(self.last_blocks(1).await?.pop());
+    }
+
+    async fn find_by_hash(&self, hash: String) -> Result<Option<BlockEntity>, String> {
+        return self
+            .store
+            .read(move |txn| {
+                let blocks: Vec<BlockEntity> = txn.find_by(BLOCKS_BY_HASH, hash.as_bytes())?;
+                return Ok(blocks.into_iter().next());
+            })
+            .await;
+    }
+
+    async fn find_recent(&self, limit: u64) -> Result<Vec<BlockEntity>, String> {
+        return self.last_blocks(limit as usize).await;
+    }
+
+    async fn insert(&self, block: BlockEntity) -> Result<ObjectId, String> {
+        return self
+            .store
+            .write(move |txn| Self::insert_one(txn, block))
+            .await;
+    }
+
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        block: BlockEntity,
+    ) -> Result<ObjectId, String> {
+        let txn = self.store.session_txn(session)?;
+        return Self::insert_one(txn, block);
+    }
+
+    async fn is_chain_valid(&self) -> Result<ChainReport, String> {
+        let (blocks, transactions) = self
+            .store
+            .read(|txn| {
+                let blocks: Vec<BlockEntity> =
+                    txn.documents(txn.index_tail(BLOCKS_BY_INDEX, usize::MAX)?)?;
+                let mut transactions = HashMap::new();
+                for id in blocks.iter().flat_map(|b| b.transactions.iter()) {
+                    if let Some(tx) = txn.document::<TransactionEntity>(id)? {
+                        transactions.insert(*id, tx);
+                    }
+                }
+                return Ok((blocks, transactions));
+            })
+            .await?;
+
+        // only Mongo nodes ran the migration that leaves legacy blocks
+        let report = chain_helper::validate_chain(&blocks, &transactions, 0, &self.setting.mining);
+        if let Some(invalid) = &report.first_invalid {
+            error!(
+                "Invalid chain at block index {}: {:?}",
+                invalid.index, invalid.violation
+            );
+        }
 
-        Ok(())
+        return Ok(report);
+    }
+
+    async fn get_last_index(&self) -> Result<u64, String> {
+        return Ok(self
+            .last_blocks(1)
+            .await?
+            .last()
+            .map(|b| b.index)
+            .unwrap_or(0));
     }
 }
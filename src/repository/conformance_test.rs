@@ -0,0 +1,495 @@
+#[cfg(test)]
+pub mod tests {
+    //! The contract of the repository traits. Every storage backend runs
+    //! the same checks, so they all behave like the Mongo one.
+
+    use std::sync::Arc;
+
+    use bson::oid::ObjectId;
+
+    use crate::{
+        app::Repositories,
+        crypto_helper::{merkle_root, transaction_hash},
+        database::database,
+        entities::{
+            address_entity::AddressEntity,
+            block_entity::BlockEntity,
+            transaction_entity::{COINBASE_ADDRESS, TransactionEntity, TransactionStatus},
+        },
+        models::transaction_model::{SignatureScheme, TransactionPayload},
+        pow_helper,
+        repository::{
+            address_repository::{BALANCE_OVERFLOW, SharedAddressRepository},
+            block_repository::SharedBlockRepository,
+            session::SharedSessionFactory,
+            transaction_repository::SharedTransactionRepository,
+        },
+        setting::{Mining, Setting},
+        timer_helper::TimerHelper,
+    };
+
+    const ADDRESS: &str = "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh";
+    const OTHER: &str = "RLkZJhcqT2ac31oqBeLvo6egULQR54LNxb";
+    const PUBLIC_KEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const OTHER_PUBLIC_KEY: &str =
+        "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+    /// Settings of a fresh `backend`, with its own redb file and Mongo
+    /// database.
+    pub fn setting(backend: &str) -> Arc<Setting> {
+        let name = format!("rust_chain_conformance_{}", ObjectId::new());
+        let path = std::env::temp_dir().join(format!("{}.redb", name));
+        let toml = include_str!("../../Settings.toml")
+            .replace("backend = \"mongo\"", &format!("backend = \"{}\"", backend))
+            .replace(
+                "path = \"rust_chain.redb\"",
+                &format!("path = {:?}", path.to_string_lossy()),
+            )
+            .replace("dbname = \"rust_chain\"", &format!("dbname = \"{}\"", name))
+            .replace("difficulty = 16", "difficulty = 1");
+        return Setting::from_toml(&toml).unwrap();
+    }
+
+    /// Runs every contract against the empty backend `setting` selects.
+    pub async fn check(setting: Arc<Setting>) {
+        let repositories = Repositories::open(Arc::clone(&setting)).await.unwrap();
+
+        address_contract(Arc::clone(&repositories.address)).await;
+        address_unique_contract(Arc::clone(&repositories.address)).await;
+        transaction_contract(Arc::clone(&repositories.transaction)).await;
+        block_contract(
+            Arc::clone(&repositories.block),
+            Arc::clone(&repositories.transaction),
+            Arc::clone(&repositories.session_factory),
+            &setting,
+        )
+        .await;
+        session_contract(&repositories).await;
+    }
+
+    fn address(address: &str) -> AddressEntity {
+        let public_key = match address {
+            ADDRESS => Some(String::from(PUBLIC_KEY)),
+            OTHER => Some(String::from(OTHER_PUBLIC_KEY)),
+            _ => None,
+        };
+        return AddressEntity::new(
+            String::from(address),
+            public_key,
+            TimerHelper::Mock.creation(),
+        );
+    }
+
+    fn transaction(
+        from: &str,
+        to: &str,
+        nonce: u64,
+        timestamp: i64,
+        status: TransactionStatus,
+    ) -> TransactionEntity {
+        let payload = TransactionPayload {
+            chain_id: 1,
+            from: String::from(from),
+            to: String::from(to),
+            amount: 1,
+            fee: 0,
+            nonce,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+        };
+        let mut tx = TransactionEntity::new(
+            &payload,
+            String::from(PUBLIC_KEY),
+            String::from("signature"),
+            status,
+            TimerHelper::Mock.creation(),
+        );
+        tx.id = Some(ObjectId::new());
+        tx.timestamp = timestamp;
+        return tx;
+    }
+
+    /// `count` mined blocks of a coinbase each, and those coinbases.
+    fn chain(count: u64, mining: &Mining) -> (Vec<BlockEntity>, Vec<TransactionEntity>) {
+        let mut blocks: Vec<BlockEntity> = Vec::new();
+        let mut txs = Vec::new();
+        for index in 1..=count {
+            let previous_hash = blocks.last().map(|b| b.hash.clone()).unwrap_or_default();
+            let mut tx = transaction(
+                COINBASE_ADDRESS,
+                OTHER,
+                index,
+                index as i64,
+                TransactionStatus::Confirmed,
+            );
+            tx.amount = mining.block_reward;
+            let leaves = vec![transaction_hash(&tx)];
+            let mut block = BlockEntity::new(
+                index,
+                vec![tx.id.unwrap()],
+                leaves.iter().map(hex::encode).collect(),
+                previous_hash,
+                hex::encode(merkle_root(&leaves)),
+                mining.difficulty,
+                TimerHelper::Mock.creation(),
+            );
+            pow_helper::mine(&mut block).unwrap();
+            blocks.push(block);
+            txs.push(tx);
+        }
+        return (blocks, txs);
+    }
+
+    fn sorted_by_id(mut txs: Vec<TransactionEntity>) -> Vec<TransactionEntity> {
+        txs.sort_by_key(|tx| tx.id);
+        return txs;
+    }
+
+    pub async fn address_contract(repository: SharedAddressRepository) {
+        assert_eq!(repository.get_by_id(ObjectId::new()).await, Ok(None));
+        assert_eq!(
+            repository.get_by_address(String::from(ADDRESS)).await,
+            Ok(None)
+        );
+
+        let mut inserted = address(ADDRESS);
+        let id = repository.insert(inserted.clone()).await.unwrap();
+        inserted.id = Some(id);
+        assert_eq!(repository.get_by_id(id).await, Ok(Some(inserted.clone())));
+        assert_eq!(
+            repository.get_by_address(String::from(ADDRESS)).await,
+            Ok(Some(inserted.clone()))
+        );
+
+        let mut updated = inserted.clone();
+        updated.updated_at = 100;
+        repository.deposit(updated.clone(), 10).await.unwrap();
+        repository.withdraw(updated.clone(), 4).await.unwrap();
+        let stored = repository.get_by_id(id).await.unwrap().unwrap();
+        assert_eq!(stored.balance, 6);
+        assert_eq!(stored.updated_at, 100);
+        assert_eq!(stored.nonce, 0);
+
+        assert_eq!(
+            repository.withdraw(updated.clone(), 7).await,
+            Err(String::from("address not found or balance is not enough"))
+        );
+        assert_eq!(repository.get_by_id(id).await.unwrap().unwrap().balance, 6);
+
+        repository
+            .deposit(updated.clone(), i64::MAX as u64 - 6)
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.deposit(updated.clone(), 1).await,
+            Err(String::from(BALANCE_OVERFLOW))
+        );
+        assert_eq!(
+            repository.get_by_id(id).await.unwrap().unwrap().balance,
+            i64::MAX as u64
+        );
+        repository
+            .withdraw(updated.clone(), i64::MAX as u64 - 6)
+            .await
+            .unwrap();
+        // an amount past i64::MAX would wrap to a change of the other sign
+        for amount in [1 << 63, u64::MAX] {
+            let error = Err(format!("amount {} is out of range", amount));
+            assert_eq!(repository.deposit(updated.clone(), amount).await, error);
+            assert_eq!(repository.withdraw(updated.clone(), amount).await, error);
+        }
+        assert_eq!(repository.get_by_id(id).await.unwrap().unwrap().balance, 6);
+
+        assert_eq!(
+            repository.deposit(address(OTHER), 1).await,
+            Err(String::from("address not found"))
+        );
+        assert_eq!(
+            repository.withdraw(address(OTHER), 1).await,
+            Err(String::from("address not found or balance is not enough"))
+        );
+        assert_eq!(
+            repository.get_by_address(String::from(OTHER)).await,
+            Ok(None)
+        );
+    }
+
+    /// The unique indexes of `database::schema`.
+    pub async fn address_unique_contract(repository: SharedAddressRepository) {
+        let mut unique = address("unique");
+        unique.public_key = Some(String::from("unique key"));
+        repository.insert(unique.clone()).await.unwrap();
+
+        assert!(repository.insert(unique.clone()).await.is_err());
+        let mut same_key = address("same key");
+        same_key.public_key = unique.public_key;
+        assert!(repository.insert(same_key).await.is_err());
+        assert_eq!(
+            repository.get_by_address(String::from("same key")).await,
+            Ok(None)
+        );
+
+        // receivers without a public key are not indexed
+        repository.insert(address("receiver a")).await.unwrap();
+        repository.insert(address("receiver b")).await.unwrap();
+    }
+
+    pub async fn transaction_contract(repository: SharedTransactionRepository) {
+        let second = transaction("a", "b", 1, 20, TransactionStatus::Pending);
+        let first = transaction("a", "b", 0, 30, TransactionStatus::Pending);
+        let old = transaction("b", "c", 0, 10, TransactionStatus::Pending);
+        let confirmed = transaction("c", "d", 0, 5, TransactionStatus::Confirmed);
+        let mut outdated = transaction("d", "a", 0, 100, TransactionStatus::Pending);
+        outdated.valid_until = Some(50);
+
+        for tx in [&second, &first, &old, &confirmed, &outdated] {
+            assert_eq!(repository.insert(tx.clone()).await, Ok(tx.id.unwrap()));
+        }
+        assert!(repository.insert(second.clone()).await.is_err());
+
+        assert_eq!(
+            repository.find_by_id(second.id.unwrap()).await,
+            Ok(Some(second.clone()))
+        );
+        assert_eq!(repository.find_by_id(ObjectId::new()).await, Ok(None));
+
+        assert_eq!(
+            repository.find_all_pending().await,
+            Ok(vec![
+                old.clone(),
+                second.clone(),
+                first.clone(),
+                outdated.clone()
+            ])
+        );
+        assert_eq!(
+            repository.find_pending_by_sender(String::from("a")).await,
+            Ok(vec![first.clone(), second.clone()])
+        );
+        assert_eq!(
+            sorted_by_id(repository.find_by_address(String::from("b")).await.unwrap()),
+            vec![second.clone(), first.clone(), old.clone()]
+        );
+        assert_eq!(
+            repository.find_by_address(String::from("unknown")).await,
+            Ok(vec![])
+        );
+
+        repository
+            .mark_confirmed(second.id.unwrap(), String::from("block"))
+            .await
+            .unwrap();
+        let stored = repository
+            .find_by_id(second.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, TransactionStatus::Confirmed);
+        assert_eq!(stored.block_hash, Some(String::from("block")));
+        assert_eq!(
+            repository.find_by_block_hash(String::from("block")).await,
+            Ok(vec![stored])
+        );
+        assert_eq!(
+            repository.find_pending_by_sender(String::from("a")).await,
+            Ok(vec![first.clone()])
+        );
+
+        repository
+            .update_status(first.id.unwrap(), TransactionStatus::Rejected)
+            .await
+            .unwrap();
+        let stored = repository
+            .find_by_id(first.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, TransactionStatus::Rejected);
+        assert_eq!(stored.block_hash, None);
+
+        assert!(
+            repository
+                .update_status(ObjectId::new(), TransactionStatus::Rejected)
+                .await
+                .is_err()
+        );
+        assert!(
+            repository
+                .mark_confirmed(ObjectId::new(), String::from("block"))
+                .await
+                .is_err()
+        );
+
+        // old was submitted too long ago and outdated is past its valid_until
+        assert_eq!(repository.expire_pending(15, 60).await, Ok(2));
+        assert_eq!(repository.find_all_pending().await, Ok(vec![]));
+        let stored = repository
+            .find_by_id(old.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, TransactionStatus::Expired);
+        assert_eq!(repository.expire_pending(15, 60).await, Ok(0));
+    }
+
+    pub async fn block_contract(
+        repository: SharedBlockRepository,
+        txs: SharedTransactionRepository,
+        sessions: SharedSessionFactory,
+        setting: &Setting,
+    ) {
+        assert_eq!(repository.find_latest().await, Ok(None));
+        assert_eq!(repository.get_last_index().await, Ok(0));
+        assert_eq!(repository.find_recent(5).await, Ok(vec![]));
+        let report = repository.is_chain_valid().await.unwrap();
+        assert!(report.valid);
+        assert_eq!(report.checked_blocks, 0);
+
+        let (mut blocks, block_txs) = chain(3, &setting.mining);
+        for tx in &block_txs {
+            txs.insert(tx.clone()).await.unwrap();
+        }
+        // out of index order, readers must order by index
+        blocks[1].id = Some(repository.insert(blocks[1].clone()).await.unwrap());
+        blocks[0].id = Some(repository.insert(blocks[0].clone()).await.unwrap());
+        let mut session = sessions.start().await.unwrap();
+        blocks[2].id = Some(
+            repository
+                .insert_with_session(&mut session, blocks[2].clone())
+                .await
+                .unwrap(),
+        );
+        assert_eq!(repository.find_latest().await, Ok(Some(blocks[1].clone())));
+        session.commit().await.unwrap();
+
+        assert_eq!(repository.find_latest().await, Ok(Some(blocks[2].clone())));
+        assert_eq!(repository.get_last_index().await, Ok(3));
+        assert_eq!(repository.find_recent(2).await, Ok(blocks[1..].to_vec()));
+        assert_eq!(repository.find_recent(10).await, Ok(blocks.clone()));
+        assert_eq!(
+            repository.find_by_hash(blocks[1].hash.clone()).await,
+            Ok(Some(blocks[1].clone()))
+        );
+        assert_eq!(repository.find_by_hash(String::from("00")).await, Ok(None));
+
+        let report = repository.is_chain_valid().await.unwrap();
+        assert!(report.valid, "{:?}", report);
+        assert_eq!(report.checked_blocks, 3);
+
+        let mut same_index = blocks[2].clone();
+        same_index.id = None;
+        same_index.hash = "ff".repeat(32);
+        assert!(repository.insert(same_index).await.is_err());
+        let mut same_hash = blocks[2].clone();
+        same_hash.id = None;
+        same_hash.index = 4;
+        assert!(repository.insert(same_hash).await.is_err());
+        assert_eq!(repository.find_latest().await, Ok(Some(blocks[2].clone())));
+    }
+
+    /// Writes of a session are only seen by the session until it commits.
+    /// The session must be the only writer, a backend may make other
+    /// writes wait for it.
+    pub async fn session_contract(repositories: &Repositories) {
+        let addresses = &repositories.address;
+        let txs = &repositories.transaction;
+        let sessions = &repositories.session_factory;
+        addresses.insert(address(OTHER)).await.unwrap();
+        addresses.deposit(address(OTHER), 10).await.unwrap();
+        let stored = || async {
+            return addresses
+                .get_by_address(String::from(OTHER))
+                .await
+                .unwrap()
+                .unwrap();
+        };
+        let tx = transaction(OTHER, ADDRESS, 0, 0, TransactionStatus::Pending);
+        let tx_id = tx.id.unwrap();
+
+        let mut session = sessions.start().await.unwrap();
+        addresses
+            .withdraw_with_session(&mut session, address(OTHER), 4)
+            .await
+            .unwrap();
+        addresses
+            .increment_nonce_with_session(&mut session, address(OTHER))
+            .await
+            .unwrap();
+        txs.insert_with_session(&mut session, tx.clone())
+            .await
+            .unwrap();
+        assert!(
+            addresses
+                .withdraw_with_session(&mut session, address(OTHER), 7)
+                .await
+                .is_err()
+        );
+        assert!(
+            addresses
+                .increment_nonce_with_session(&mut session, address("unknown"))
+                .await
+                .is_err()
+        );
+        assert_eq!((stored().await.balance, stored().await.nonce), (10, 0));
+        assert_eq!(txs.find_by_id(tx_id).await, Ok(None));
+        session.commit().await.unwrap();
+        // like the Mongo driver, a second commit retries the first one and
+        // writes nothing again, aborting a committed session fails
+        session.commit().await.unwrap();
+        assert!(session.abort().await.is_err());
+
+        assert_eq!((stored().await.balance, stored().await.nonce), (6, 1));
+        assert_eq!(txs.find_by_id(tx_id).await, Ok(Some(tx.clone())));
+
+        let mut session = sessions.start().await.unwrap();
+        addresses
+            .deposit_with_session(&mut session, address(OTHER), 100)
+            .await
+            .unwrap();
+        txs.mark_confirmed_with_session(&mut session, tx_id, String::from("block"))
+            .await
+            .unwrap();
+        txs.update_status_with_session(&mut session, tx_id, TransactionStatus::Invalid)
+            .await
+            .unwrap();
+        assert!(
+            txs.update_status_with_session(
+                &mut session,
+                ObjectId::new(),
+                TransactionStatus::Invalid
+            )
+            .await
+            .is_err()
+        );
+        session.abort().await.unwrap();
+        assert!(session.abort().await.is_err());
+
+        assert_eq!(stored().await.balance, 6);
+        assert_eq!(txs.find_by_id(tx_id).await, Ok(Some(tx)));
+    }
+
+    #[tokio::test]
+    async fn memory_conformance_test() {
+        check(setting("memory")).await;
+    }
+
+    #[tokio::test]
+    async fn redb_conformance_test() {
+        let setting = setting("redb");
+        check(Arc::clone(&setting)).await;
+        let _ = std::fs::remove_file(&setting.storage.path);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the MongoDB replica set from docker-compose.yml"]
+    async fn mongo_conformance_test() {
+        let setting = setting("mongo");
+        check(Arc::clone(&setting)).await;
+        database::db_connect(setting)
+            .await
+            .unwrap()
+            .drop()
+            .await
+            .unwrap();
+    }
+}
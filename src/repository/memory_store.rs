@@ -0,0 +1,261 @@
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use tracing::error;
+
+use super::session::{BoxedSession, RepositorySession, SessionFactory, SharedSessionFactory};
+use crate::entities::{
+    address_entity::AddressEntity, block_entity::BlockEntity, transaction_entity::TransactionEntity,
+};
+
+/// Documents of every collection keyed by `_id`. Object ids grow with
+/// time, so iterating a collection visits documents in insertion order
+/// like a Mongo scan without a sort.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemoryState {
+    pub addresses: BTreeMap<ObjectId, AddressEntity>,
+    pub transactions: BTreeMap<ObjectId, TransactionEntity>,
+    pub blocks: BTreeMap<ObjectId, BlockEntity>,
+}
+
+/// Thread-safe in-memory database shared by the memory repositories and
+/// their sessions. Nothing is persisted.
+#[derive(Default)]
+pub struct MemoryStore {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryStore {
+    pub fn creation() -> Arc<Self> {
+        return Arc::new(Self::default());
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        // a panic while holding the lock leaves the state as consistent as
+        // a crash would leave Mongo, keep serving it
+        return self.state.lock().unwrap_or_else(PoisonError::into_inner);
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&MemoryState) -> R) -> R {
+        return f(&self.lock());
+    }
+
+    pub fn write<R>(&self, f: impl FnOnce(&mut MemoryState) -> R) -> R {
+        return f(&mut self.lock());
+    }
+
+    /// The working state of a session started on this store.
+    pub fn session_state<'a>(
+        self: &Arc<Self>,
+        session: &'a mut BoxedSession,
+    ) -> Result<&'a mut MemoryState, String> {
+        let session = memory_session(session)?;
+        if !Arc::ptr_eq(&session.store, self) {
+            error!("session belongs to another memory store");
+            return Err("session belongs to another memory store".to_string());
+        }
+        if session.finished {
+            return Err("session transaction already finished".to_string());
+        }
+        return Ok(&mut session.working);
+    }
+}
+
+/// Inserts `value` under `id`, failing like a unique `_id` index would.
+pub fn insert_document<T>(
+    collection: &mut BTreeMap<ObjectId, T>,
+    id: ObjectId,
+    value: T,
+) -> Result<ObjectId, String> {
+    if collection.contains_key(&id) {
+        error!("insert failed, duplicate _id {}", id);
+        return Err(format!("duplicate key _id: {}", id));
+    }
+    collection.insert(id, value);
+    return Ok(id);
+}
+
+/// Fails like a unique index on `field` would when a document other than
+/// `id` already holds `value`. A missing value is not indexed.
+fn ensure_unique<T, V: PartialEq + Display>(
+    collection: &BTreeMap<ObjectId, T>,
+    id: ObjectId,
+    field: &str,
+    value: Option<V>,
+    key: impl Fn(&T) -> Option<V>,
+) -> Result<(), String> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    if collection
+        .iter()
+        .any(|(other, document)| *other != id && key(document).as_ref() == Some(&value))
+    {
+        error!("duplicate key {}: {}", field, value);
+        return Err(format!("duplicate key {}: {}", field, value));
+    }
+    return Ok(());
+}
+
+/// The unique indexes `database::schema` creates on `addresses`.
+pub fn ensure_unique_address(
+    addresses: &BTreeMap<ObjectId, AddressEntity>,
+    id: ObjectId,
+    address: &AddressEntity,
+) -> Result<(), String> {
+    ensure_unique(
+        addresses,
+        id,
+        "address",
+        Some(address.address.clone()),
+        |a| Some(a.address.clone()),
+    )?;
+    return ensure_unique(
+        addresses,
+        id,
+        "public_key",
+        address.public_key.clone(),
+        |a| a.public_key.clone(),
+    );
+}
+
+/// The unique indexes `database::schema` creates on `blocks`.
+pub fn ensure_unique_block(
+    blocks: &BTreeMap<ObjectId, BlockEntity>,
+    id: ObjectId,
+    block: &BlockEntity,
+) -> Result<(), String> {
+    ensure_unique(blocks, id, "hash", Some(block.hash.clone()), |b| {
+        Some(b.hash.clone())
+    })?;
+    return ensure_unique(blocks, id, "index", Some(block.index), |b| Some(b.index));
+}
+
+/// A snapshot of the store taken when the session starts. Writes go to a
+/// working copy and are applied on commit unless a document they touch was
+/// changed by someone else in the meantime, the same write conflict a
+/// Mongo transaction reports.
+pub struct MemorySession {
+    store: Arc<MemoryStore>,
+    snapshot: MemoryState,
+    working: MemoryState,
+    finished: bool,
+    committed: bool,
+}
+
+/// Applies the documents `working` changed since `snapshot` to `committed`,
+/// or fails without writing anything when `committed` moved on.
+fn merge<T: Clone + PartialEq>(
+    committed: &BTreeMap<ObjectId, T>,
+    snapshot: &BTreeMap<ObjectId, T>,
+    working: &BTreeMap<ObjectId, T>,
+) -> Result<Vec<(ObjectId, T)>, String> {
+    let mut changes = Vec::new();
+    for (id, value) in working {
+        let before = snapshot.get(id);
+        if before == Some(value) {
+            continue;
+        }
+        if committed.get(id) != before {
+            error!("write conflict on document {}", id);
+            return Err(format!("write conflict on document {}", id));
+        }
+        changes.push((*id, value.clone()));
+    }
+    return Ok(changes);
+}
+
+#[async_trait]
+impl RepositorySession for MemorySession {
+    async fn commit(&mut self) -> Result<(), String> {
+        // like the Mongo driver, committing again retries the commit, which
+        // already succeeded
+        if self.committed {
+            return Ok(());
+        }
+        if self.finished {
+            return Err("session transaction already finished".to_string());
+        }
+        self.finished = true;
+
+        let mut state = self.store.lock();
+        let addresses = merge(
+            &state.addresses,
+            &self.snapshot.addresses,
+            &self.working.addresses,
+        )?;
+        let transactions = merge(
+            &state.transactions,
+            &self.snapshot.transactions,
+            &self.working.transactions,
+        )?;
+        let blocks = merge(&state.blocks, &self.snapshot.blocks, &self.working.blocks)?;
+        // another writer may have taken a unique key since the snapshot
+        for (id, address) in &addresses {
+            ensure_unique_address(&state.addresses, *id, address)?;
+        }
+        for (id, block) in &blocks {
+            ensure_unique_block(&state.blocks, *id, block)?;
+        }
+
+        state.addresses.extend(addresses);
+        state.transactions.extend(transactions);
+        state.blocks.extend(blocks);
+        self.committed = true;
+        return Ok(());
+    }
+
+    async fn abort(&mut self) -> Result<(), String> {
+        if self.finished {
+            return Err("session transaction already finished".to_string());
+        }
+        self.finished = true;
+        return Ok(());
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
+}
+
+/// Returns the underlying memory session, failing when a session from
+/// another backend is handed to a memory repository.
+pub fn memory_session(session: &mut BoxedSession) -> Result<&mut MemorySession, String> {
+    return match session.as_any_mut().downcast_mut::<MemorySession>() {
+        Some(s) => Ok(s),
+        None => {
+            error!("session is not a memory session");
+            Err("session is not a memory session".to_string())
+        }
+    };
+}
+
+pub struct MemorySessionFactory {
+    store: Arc<MemoryStore>,
+}
+
+impl MemorySessionFactory {
+    pub fn creation(store: Arc<MemoryStore>) -> SharedSessionFactory {
+        return Arc::new(Self { store });
+    }
+}
+
+#[async_trait]
+impl SessionFactory for MemorySessionFactory {
+    async fn start(&self) -> Result<BoxedSession, String> {
+        let snapshot = self.store.read(|state| state.clone());
+        return Ok(Box::new(MemorySession {
+            store: Arc::clone(&self.store),
+            working: snapshot.clone(),
+            snapshot,
+            finished: false,
+            committed: false,
+        }));
+    }
+}
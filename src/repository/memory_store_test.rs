@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        entities::{address_entity::AddressEntity, block_entity::BlockEntity},
+        repository::{
+            address_repository::{MemoryAddressRepository, SharedAddressRepository},
+            block_repository::MemoryBlockRepository,
+            memory_store::{MemorySessionFactory, MemoryStore},
+            session::SharedSessionFactory,
+        },
+        setting::Setting,
+        timer_helper::TimerHelper,
+    };
+
+    const ADDRESS: &str = "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh";
+
+    async fn funded(balance: u64) -> (SharedAddressRepository, SharedSessionFactory) {
+        let store = MemoryStore::creation();
+        let repository = MemoryAddressRepository::creation(Arc::clone(&store));
+        repository.insert(address()).await.unwrap();
+        repository.deposit(address(), balance).await.unwrap();
+        return (repository, MemorySessionFactory::creation(store));
+    }
+
+    fn address() -> AddressEntity {
+        return AddressEntity::new(String::from(ADDRESS), None, TimerHelper::Mock.creation());
+    }
+
+    async fn balance(repository: &SharedAddressRepository) -> u64 {
+        return repository
+            .get_by_address(String::from(ADDRESS))
+            .await
+            .unwrap()
+            .unwrap()
+            .balance;
+    }
+
+    #[tokio::test]
+    async fn session_commit_test() {
+        let (repository, sessions) = funded(10).await;
+        let mut session = sessions.start().await.unwrap();
+
+        repository
+            .withdraw_with_session(&mut session, address(), 4)
+            .await
+            .unwrap();
+        assert_eq!(balance(&repository).await, 10);
+
+        session.commit().await.unwrap();
+        assert_eq!(balance(&repository).await, 6);
+        // a second commit is a retry and writes nothing again
+        session.commit().await.unwrap();
+        assert_eq!(balance(&repository).await, 6);
+        assert!(session.abort().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn session_abort_test() {
+        let (repository, sessions) = funded(10).await;
+        let mut session = sessions.start().await.unwrap();
+
+        repository
+            .withdraw_with_session(&mut session, address(), 4)
+            .await
+            .unwrap();
+        session.abort().await.unwrap();
+
+        assert_eq!(balance(&repository).await, 10);
+        assert!(
+            repository
+                .withdraw_with_session(&mut session, address(), 1)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn session_sees_own_writes_test() {
+        let (repository, sessions) = funded(10).await;
+        let mut session = sessions.start().await.unwrap();
+
+        repository
+            .withdraw_with_session(&mut session, address(), 6)
+            .await
+            .unwrap();
+
+        assert!(
+            repository
+                .withdraw_with_session(&mut session, address(), 6)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn session_write_conflict_test() {
+        let (repository, sessions) = funded(10).await;
+        let mut session = sessions.start().await.unwrap();
+
+        repository
+            .withdraw_with_session(&mut session, address(), 4)
+            .await
+            .unwrap();
+        repository.deposit(address(), 1).await.unwrap();
+
+        assert!(session.commit().await.is_err());
+        assert_eq!(balance(&repository).await, 11);
+    }
+
+    #[tokio::test]
+    async fn session_from_other_store_test() {
+        let (repository, _) = funded(10).await;
+        let (_, other_sessions) = funded(10).await;
+        let mut session = other_sessions.start().await.unwrap();
+
+        assert!(
+            repository
+                .deposit_with_session(&mut session, address(), 1)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn session_unique_key_conflict_test() {
+        let store = MemoryStore::creation();
+        let setting = Setting::from_toml(include_str!("../../Settings.toml")).unwrap();
+        let repository = MemoryBlockRepository::creation(Arc::clone(&store), setting);
+        let sessions = MemorySessionFactory::creation(store);
+        let block = |hash: &str| {
+            let mut block = BlockEntity::new(
+                1,
+                vec![],
+                vec![],
+                String::new(),
+                String::new(),
+                1,
+                TimerHelper::Mock.creation(),
+            );
+            block.hash = String::from(hash);
+            return block;
+        };
+
+        let mut first = sessions.start().await.unwrap();
+        let mut second = sessions.start().await.unwrap();
+        repository
+            .insert_with_session(&mut first, block("aa"))
+            .await
+            .unwrap();
+        repository
+            .insert_with_session(&mut second, block("bb"))
+            .await
+            .unwrap();
+        first.commit().await.unwrap();
+
+        assert!(second.commit().await.is_err());
+        assert_eq!(repository.find_recent(10).await.unwrap().len(), 1);
+    }
+}
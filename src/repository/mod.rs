@@ -1,3 +1,9 @@
 pub mod address_repository;
 pub mod transaction_repository;
-pub mod block_repository;
\ No newline at end of file
+pub mod block_repository;
+pub mod session;
+pub mod memory_store;
+pub mod memory_store_test;
+pub mod redb_store;
+pub mod redb_store_test;
+pub mod conformance_test;
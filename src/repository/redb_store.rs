@@ -0,0 +1,523 @@
+use std::{any::Any, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use redb::{
+    Database, MultimapTableDefinition, MultimapTableHandle, ReadTransaction, ReadableMultimapTable,
+    ReadableTable, TableDefinition, WriteTransaction,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::error;
+
+use super::session::{BoxedSession, RepositorySession, SessionFactory, SharedSessionFactory};
+use crate::entities::{
+    address_entity::AddressEntity,
+    block_entity::BlockEntity,
+    transaction_entity::{TransactionEntity, TransactionStatus},
+};
+
+/// BSON encoded documents keyed by the 12 bytes of their `_id`.
+pub type DocumentTable = TableDefinition<'static, &'static [u8], &'static [u8]>;
+/// Secondary index from a field value to the `_id`s of the documents
+/// holding it. Values of a key are ordered by `_id`, so by insertion.
+pub type IndexTable = MultimapTableDefinition<'static, &'static [u8], &'static [u8]>;
+
+pub const ADDRESSES: DocumentTable = TableDefinition::new("addresses");
+pub const ADDRESSES_BY_ADDRESS: IndexTable = MultimapTableDefinition::new("addresses.address");
+pub const ADDRESSES_BY_PUBLIC_KEY: IndexTable =
+    MultimapTableDefinition::new("addresses.public_key");
+
+pub const TRANSACTIONS: DocumentTable = TableDefinition::new("transactions");
+pub const TRANSACTIONS_BY_FROM: IndexTable = MultimapTableDefinition::new("transactions.from");
+pub const TRANSACTIONS_BY_TO: IndexTable = MultimapTableDefinition::new("transactions.to");
+pub const TRANSACTIONS_BY_STATUS: IndexTable = MultimapTableDefinition::new("transactions.status");
+pub const TRANSACTIONS_BY_BLOCK_HASH: IndexTable =
+    MultimapTableDefinition::new("transactions.block_hash");
+
+pub const BLOCKS: DocumentTable = TableDefinition::new("blocks");
+pub const BLOCKS_BY_HASH: IndexTable = MultimapTableDefinition::new("blocks.hash");
+/// Keyed by the big-endian index, so the last key is the chain tip.
+pub const BLOCKS_BY_INDEX: IndexTable = MultimapTableDefinition::new("blocks.index");
+
+const DOCUMENT_TABLES: [DocumentTable; 3] = [ADDRESSES, TRANSACTIONS, BLOCKS];
+const INDEX_TABLES: [IndexTable; 8] = [
+    ADDRESSES_BY_ADDRESS,
+    ADDRESSES_BY_PUBLIC_KEY,
+    TRANSACTIONS_BY_FROM,
+    TRANSACTIONS_BY_TO,
+    TRANSACTIONS_BY_STATUS,
+    TRANSACTIONS_BY_BLOCK_HASH,
+    BLOCKS_BY_HASH,
+    BLOCKS_BY_INDEX,
+];
+
+/// An entry of a secondary index pointing at a document.
+pub struct IndexKey {
+    pub index: IndexTable,
+    pub key: Vec<u8>,
+    /// Shown in duplicate key errors.
+    pub value: String,
+    /// Refuses a second document under `key`, like the unique indexes
+    /// `database::schema` creates in Mongo.
+    pub unique: bool,
+}
+
+impl IndexKey {
+    pub fn text(index: IndexTable, value: &str, unique: bool) -> Self {
+        return Self {
+            index,
+            key: value.as_bytes().to_vec(),
+            value: value.to_string(),
+            unique,
+        };
+    }
+
+    /// Big-endian, so keys sort like the numbers.
+    pub fn number(index: IndexTable, value: u64, unique: bool) -> Self {
+        return Self {
+            index,
+            key: value.to_be_bytes().to_vec(),
+            value: value.to_string(),
+            unique,
+        };
+    }
+}
+
+/// A document stored in its own table and kept in sync with its indexes.
+pub trait RedbDocument: Serialize + DeserializeOwned {
+    const TABLE: DocumentTable;
+
+    fn id(&self) -> Option<ObjectId>;
+
+    fn index_keys(&self) -> Vec<IndexKey>;
+}
+
+impl RedbDocument for AddressEntity {
+    const TABLE: DocumentTable = ADDRESSES;
+
+    fn id(&self) -> Option<ObjectId> {
+        return self.id;
+    }
+
+    fn index_keys(&self) -> Vec<IndexKey> {
+        let mut keys = vec![IndexKey::text(ADDRESSES_BY_ADDRESS, &self.address, true)];
+        if let Some(public_key) = &self.public_key {
+            keys.push(IndexKey::text(ADDRESSES_BY_PUBLIC_KEY, public_key, true));
+        }
+        return keys;
+    }
+}
+
+impl RedbDocument for TransactionEntity {
+    const TABLE: DocumentTable = TRANSACTIONS;
+
+    fn id(&self) -> Option<ObjectId> {
+        return self.id;
+    }
+
+    fn index_keys(&self) -> Vec<IndexKey> {
+        let mut keys = vec![
+            IndexKey::text(TRANSACTIONS_BY_FROM, &self.from, false),
+            IndexKey::text(TRANSACTIONS_BY_TO, &self.to, false),
+            IndexKey::text(TRANSACTIONS_BY_STATUS, status_name(&self.status), false),
+        ];
+        if let Some(block_hash) = &self.block_hash {
+            keys.push(IndexKey::text(
+                TRANSACTIONS_BY_BLOCK_HASH,
+                block_hash,
+                false,
+            ));
+        }
+        return keys;
+    }
+}
+
+impl RedbDocument for BlockEntity {
+    const TABLE: DocumentTable = BLOCKS;
+
+    fn id(&self) -> Option<ObjectId> {
+        return self.id;
+    }
+
+    fn index_keys(&self) -> Vec<IndexKey> {
+        return vec![
+            IndexKey::text(BLOCKS_BY_HASH, &self.hash, true),
+            IndexKey::number(BLOCKS_BY_INDEX, self.index, true),
+        ];
+    }
+}
+
+/// Key of `status` in `TRANSACTIONS_BY_STATUS`, its serialized name.
+pub fn status_name(status: &TransactionStatus) -> &'static str {
+    return match status {
+        TransactionStatus::Pending => "pending",
+        TransactionStatus::Confirmed => "confirmed",
+        TransactionStatus::Rejected => "rejected",
+        TransactionStatus::Invalid => "invalid",
+        TransactionStatus::Expired => "expired",
+    };
+}
+
+fn storage_error(e: impl Into<redb::Error>) -> String {
+    let e: redb::Error = e.into();
+    error!("redb error: {}", e);
+    return e.to_string();
+}
+
+fn object_id(bytes: &[u8]) -> Result<ObjectId, String> {
+    return match <[u8; 12]>::try_from(bytes) {
+        Ok(bytes) => Ok(ObjectId::from_bytes(bytes)),
+        Err(_) => {
+            error!("corrupted _id of {} bytes", bytes.len());
+            Err("corrupted _id".to_string())
+        }
+    };
+}
+
+fn decode<D: RedbDocument>(bytes: &[u8]) -> Result<D, String> {
+    return bson::from_slice(bytes).map_err(|e| {
+        error!("convert bson to document failed: {}", e);
+        return e.to_string();
+    });
+}
+
+fn get_document<D: RedbDocument>(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    id: &ObjectId,
+) -> Result<Option<D>, String> {
+    return match table.get(id.bytes().as_slice()).map_err(storage_error)? {
+        Some(bytes) => decode(bytes.value()).map(Some),
+        None => Ok(None),
+    };
+}
+
+fn index_ids(
+    index: &impl ReadableMultimapTable<&'static [u8], &'static [u8]>,
+    key: &[u8],
+) -> Result<Vec<ObjectId>, String> {
+    let mut ids = Vec::new();
+    for id in index.get(key).map_err(storage_error)? {
+        ids.push(object_id(id.map_err(storage_error)?.value())?);
+    }
+    return Ok(ids);
+}
+
+fn index_tail(
+    index: &impl ReadableMultimapTable<&'static [u8], &'static [u8]>,
+    limit: usize,
+) -> Result<Vec<ObjectId>, String> {
+    let mut ids = Vec::new();
+    'keys: for entry in index.iter().map_err(storage_error)?.rev() {
+        let (_, values) = entry.map_err(storage_error)?;
+        for id in values.rev() {
+            if ids.len() == limit {
+                break 'keys;
+            }
+            ids.push(object_id(id.map_err(storage_error)?.value())?);
+        }
+    }
+    ids.reverse();
+    return Ok(ids);
+}
+
+/// Lookups shared by read transactions and the write transaction of a
+/// session, which sees its own writes.
+pub trait RedbRead {
+    fn document<D: RedbDocument>(&self, id: &ObjectId) -> Result<Option<D>, String>;
+
+    /// `_id`s under `key` in `index`, in insertion order.
+    fn index_ids(&self, index: IndexTable, key: &[u8]) -> Result<Vec<ObjectId>, String>;
+
+    /// The last `limit` `_id`s of `index` in key order.
+    fn index_tail(&self, index: IndexTable, limit: usize) -> Result<Vec<ObjectId>, String>;
+
+    /// The documents under `key` in `index`, in insertion order.
+    fn find_by<D: RedbDocument>(&self, index: IndexTable, key: &[u8]) -> Result<Vec<D>, String> {
+        return self.documents(self.index_ids(index, key)?);
+    }
+
+    fn documents<D: RedbDocument>(&self, ids: Vec<ObjectId>) -> Result<Vec<D>, String> {
+        let mut documents = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.document(&id)? {
+                Some(document) => documents.push(document),
+                None => {
+                    error!("index points at missing document {}", id);
+                    return Err(format!("index points at missing document {}", id));
+                }
+            }
+        }
+        return Ok(documents);
+    }
+}
+
+impl RedbRead for ReadTransaction {
+    fn document<D: RedbDocument>(&self, id: &ObjectId) -> Result<Option<D>, String> {
+        return get_document(&self.open_table(D::TABLE).map_err(storage_error)?, id);
+    }
+
+    fn index_ids(&self, index: IndexTable, key: &[u8]) -> Result<Vec<ObjectId>, String> {
+        return index_ids(
+            &self.open_multimap_table(index).map_err(storage_error)?,
+            key,
+        );
+    }
+
+    fn index_tail(&self, index: IndexTable, limit: usize) -> Result<Vec<ObjectId>, String> {
+        return index_tail(
+            &self.open_multimap_table(index).map_err(storage_error)?,
+            limit,
+        );
+    }
+}
+
+impl RedbRead for WriteTransaction {
+    fn document<D: RedbDocument>(&self, id: &ObjectId) -> Result<Option<D>, String> {
+        return get_document(&self.open_table(D::TABLE).map_err(storage_error)?, id);
+    }
+
+    fn index_ids(&self, index: IndexTable, key: &[u8]) -> Result<Vec<ObjectId>, String> {
+        return index_ids(
+            &self.open_multimap_table(index).map_err(storage_error)?,
+            key,
+        );
+    }
+
+    fn index_tail(&self, index: IndexTable, limit: usize) -> Result<Vec<ObjectId>, String> {
+        return index_tail(
+            &self.open_multimap_table(index).map_err(storage_error)?,
+            limit,
+        );
+    }
+}
+
+/// Writes `document` under its `_id` and moves its index entries from the
+/// stored version, if any, to the new one.
+fn put_document<D: RedbDocument>(
+    txn: &WriteTransaction,
+    document: &D,
+    overwrite: bool,
+) -> Result<ObjectId, String> {
+    let Some(id) = document.id() else {
+        error!("cannot store a document without _id");
+        return Err("cannot store a document without _id".to_string());
+    };
+    let key = id.bytes();
+    let bytes = bson::to_vec(document).map_err(|e| {
+        error!("convert document to bson failed: {}", e);
+        return e.to_string();
+    })?;
+
+    let previous: Option<D> = {
+        let mut table = txn.open_table(D::TABLE).map_err(storage_error)?;
+        let previous = get_document(&table, &id)?;
+        if previous.is_some() && !overwrite {
+            error!("insert failed, duplicate _id {}", id);
+            return Err(format!("duplicate key _id: {}", id));
+        }
+        table
+            .insert(key.as_slice(), bytes.as_slice())
+            .map_err(storage_error)?;
+        previous
+    };
+
+    if let Some(previous) = previous {
+        for entry in previous.index_keys() {
+            let mut index = txn
+                .open_multimap_table(entry.index)
+                .map_err(storage_error)?;
+            index
+                .remove(entry.key.as_slice(), key.as_slice())
+                .map_err(storage_error)?;
+        }
+    }
+    for entry in document.index_keys() {
+        let mut index = txn
+            .open_multimap_table(entry.index)
+            .map_err(storage_error)?;
+        if entry.unique
+            && index_ids(&index, &entry.key)?
+                .iter()
+                .any(|other| *other != id)
+        {
+            // the name of an index table is "<collection>.<field>"
+            let name = entry.index.name();
+            let field = name.rsplit('.').next().unwrap_or(name);
+            error!("duplicate key {}: {}", field, entry.value);
+            return Err(format!("duplicate key {}: {}", field, entry.value));
+        }
+        index
+            .insert(entry.key.as_slice(), key.as_slice())
+            .map_err(storage_error)?;
+    }
+    return Ok(id);
+}
+
+/// Inserts `document`, failing like a unique `_id` index would.
+pub fn insert_document<D: RedbDocument>(
+    txn: &WriteTransaction,
+    document: &D,
+) -> Result<ObjectId, String> {
+    return put_document(txn, document, false);
+}
+
+/// Replaces the stored version of `document`.
+pub fn replace_document<D: RedbDocument>(
+    txn: &WriteTransaction,
+    document: &D,
+) -> Result<(), String> {
+    return put_document(txn, document, true).map(|_| ());
+}
+
+/// Embedded database file shared by the redb repositories and their
+/// sessions. redb has a single writer, so a write waits for a running
+/// session to finish while reads never wait.
+pub struct RedbStore {
+    db: Database,
+}
+
+impl RedbStore {
+    /// Opens the database at `path`, creating the file and its tables when
+    /// missing.
+    pub fn open(path: impl AsRef<Path>) -> Result<Arc<Self>, String> {
+        let db = Database::create(path).map_err(storage_error)?;
+        let txn = db.begin_write().map_err(storage_error)?;
+        for table in DOCUMENT_TABLES {
+            txn.open_table(table).map_err(storage_error)?;
+        }
+        for index in INDEX_TABLES {
+            txn.open_multimap_table(index).map_err(storage_error)?;
+        }
+        txn.commit().map_err(storage_error)?;
+        return Ok(Arc::new(Self { db }));
+    }
+
+    /// Runs `f` in a read transaction off the async runtime.
+    pub async fn read<R: Send + 'static>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&ReadTransaction) -> Result<R, String> + Send + 'static,
+    ) -> Result<R, String> {
+        let store = Arc::clone(self);
+        return blocking(move || {
+            let txn = store.db.begin_read().map_err(storage_error)?;
+            return f(&txn);
+        })
+        .await;
+    }
+
+    /// Runs `f` in a write transaction off the async runtime and commits it
+    /// when `f` succeeds.
+    pub async fn write<R: Send + 'static>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&WriteTransaction) -> Result<R, String> + Send + 'static,
+    ) -> Result<R, String> {
+        let store = Arc::clone(self);
+        return blocking(move || {
+            let txn = store.db.begin_write().map_err(storage_error)?;
+            let result = f(&txn);
+            match result {
+                Ok(_) => txn.commit().map_err(storage_error)?,
+                Err(_) => txn.abort().map_err(storage_error)?,
+            }
+            return result;
+        })
+        .await;
+    }
+
+    /// The write transaction of a session started on this store.
+    pub fn session_txn<'a>(
+        self: &Arc<Self>,
+        session: &'a mut BoxedSession,
+    ) -> Result<&'a WriteTransaction, String> {
+        let session = redb_session(session)?;
+        if !Arc::ptr_eq(&session.store, self) {
+            error!("session belongs to another redb store");
+            return Err("session belongs to another redb store".to_string());
+        }
+        return match &session.txn {
+            Some(txn) => Ok(txn),
+            None => Err("session transaction already finished".to_string()),
+        };
+    }
+}
+
+async fn blocking<R: Send + 'static>(
+    f: impl FnOnce() -> Result<R, String> + Send + 'static,
+) -> Result<R, String> {
+    return tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+}
+
+/// A redb write transaction. Until it commits or aborts no other writer
+/// runs, so a session never conflicts.
+pub struct RedbSession {
+    store: Arc<RedbStore>,
+    txn: Option<WriteTransaction>,
+    committed: bool,
+}
+
+#[async_trait]
+impl RepositorySession for RedbSession {
+    async fn commit(&mut self) -> Result<(), String> {
+        // like the Mongo driver, committing again retries the commit, which
+        // already succeeded
+        if self.committed {
+            return Ok(());
+        }
+        let Some(txn) = self.txn.take() else {
+            return Err("session transaction already finished".to_string());
+        };
+        blocking(move || txn.commit().map_err(storage_error)).await?;
+        self.committed = true;
+        return Ok(());
+    }
+
+    async fn abort(&mut self) -> Result<(), String> {
+        let Some(txn) = self.txn.take() else {
+            return Err("session transaction already finished".to_string());
+        };
+        return txn.abort().map_err(storage_error);
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
+}
+
+/// Returns the underlying redb session, failing when a session from
+/// another backend is handed to a redb repository.
+pub fn redb_session(session: &mut BoxedSession) -> Result<&mut RedbSession, String> {
+    return match session.as_any_mut().downcast_mut::<RedbSession>() {
+        Some(s) => Ok(s),
+        None => {
+            error!("session is not a redb session");
+            Err("session is not a redb session".to_string())
+        }
+    };
+}
+
+pub struct RedbSessionFactory {
+    store: Arc<RedbStore>,
+}
+
+impl RedbSessionFactory {
+    pub fn creation(store: Arc<RedbStore>) -> SharedSessionFactory {
+        return Arc::new(Self { store });
+    }
+}
+
+#[async_trait]
+impl SessionFactory for RedbSessionFactory {
+    async fn start(&self) -> Result<BoxedSession, String> {
+        // waits for the session or write running, if any
+        let store = Arc::clone(&self.store);
+        let txn = blocking(move || store.db.begin_write().map_err(storage_error)).await?;
+        return Ok(Box::new(RedbSession {
+            store: Arc::clone(&self.store),
+            txn: Some(txn),
+            committed: false,
+        }));
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use bson::oid::ObjectId;
+
+    use crate::{
+        entities::{
+            address_entity::AddressEntity,
+            transaction_entity::{TransactionEntity, TransactionStatus},
+        },
+        models::transaction_model::{SignatureScheme, TransactionPayload},
+        repository::{
+            address_repository::{
+                BALANCE_OVERFLOW, RedbAddressRepository, SharedAddressRepository,
+            },
+            redb_store::{RedbSessionFactory, RedbStore},
+            session::SharedSessionFactory,
+            transaction_repository::RedbTransactionRepository,
+        },
+        timer_helper::TimerHelper,
+    };
+
+    const ADDRESS: &str = "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh";
+
+    /// A database file of its own, removed when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new() -> Self {
+            let name = format!("rust_chain_test_{}.redb", ObjectId::new());
+            return Self(std::env::temp_dir().join(name));
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    async fn funded(
+        path: &TempPath,
+        balance: u64,
+    ) -> (SharedAddressRepository, SharedSessionFactory) {
+        let store = RedbStore::open(&path.0).unwrap();
+        let repository = RedbAddressRepository::creation(Arc::clone(&store));
+        repository.insert(address()).await.unwrap();
+        repository.deposit(address(), balance).await.unwrap();
+        return (repository, RedbSessionFactory::creation(store));
+    }
+
+    fn address() -> AddressEntity {
+        return AddressEntity::new(String::from(ADDRESS), None, TimerHelper::Mock.creation());
+    }
+
+    async fn balance(repository: &SharedAddressRepository) -> u64 {
+        return repository
+            .get_by_address(String::from(ADDRESS))
+            .await
+            .unwrap()
+            .unwrap()
+            .balance;
+    }
+
+    #[tokio::test]
+    async fn session_commit_test() {
+        let path = TempPath::new();
+        let (repository, sessions) = funded(&path, 10).await;
+        let mut session = sessions.start().await.unwrap();
+
+        repository
+            .withdraw_with_session(&mut session, address(), 4)
+            .await
+            .unwrap();
+        assert_eq!(balance(&repository).await, 10);
+
+        session.commit().await.unwrap();
+        assert_eq!(balance(&repository).await, 6);
+        // a second commit is a retry and writes nothing again
+        session.commit().await.unwrap();
+        assert_eq!(balance(&repository).await, 6);
+        assert!(session.abort().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn session_abort_test() {
+        let path = TempPath::new();
+        let (repository, sessions) = funded(&path, 10).await;
+        let mut session = sessions.start().await.unwrap();
+
+        repository
+            .withdraw_with_session(&mut session, address(), 6)
+            .await
+            .unwrap();
+        assert!(
+            repository
+                .withdraw_with_session(&mut session, address(), 6)
+                .await
+                .is_err()
+        );
+        session.abort().await.unwrap();
+
+        assert_eq!(balance(&repository).await, 10);
+    }
+
+    #[tokio::test]
+    async fn deposit_overflow_test() {
+        let path = TempPath::new();
+        let (repository, _) = funded(&path, i64::MAX as u64).await;
+
+        assert_eq!(
+            repository.deposit(address(), 1).await,
+            Err(String::from(BALANCE_OVERFLOW))
+        );
+        assert_eq!(balance(&repository).await, i64::MAX as u64);
+    }
+
+    #[tokio::test]
+    async fn data_survives_reopen_test() {
+        let path = TempPath::new();
+        {
+            let (repository, _) = funded(&path, 10).await;
+            repository.withdraw(address(), 3).await.unwrap();
+        }
+
+        let store = RedbStore::open(&path.0).unwrap();
+        let repository = RedbAddressRepository::creation(store);
+        assert_eq!(balance(&repository).await, 7);
+    }
+
+    #[tokio::test]
+    async fn status_index_follows_updates_test() {
+        let path = TempPath::new();
+        let repository = RedbTransactionRepository::creation(RedbStore::open(&path.0).unwrap());
+        let payload = TransactionPayload {
+            chain_id: 1,
+            from: String::from(ADDRESS),
+            to: String::from("receiver"),
+            amount: 1,
+            fee: 0,
+            nonce: 0,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+        };
+        let mut tx = TransactionEntity::new(
+            &payload,
+            String::new(),
+            String::new(),
+            TransactionStatus::Pending,
+            TimerHelper::Mock.creation(),
+        );
+        tx.id = Some(ObjectId::new());
+
+        let id = repository.insert(tx.clone()).await.unwrap();
+        assert!(repository.insert(tx).await.is_err());
+        assert_eq!(repository.find_all_pending().await.unwrap().len(), 1);
+
+        repository
+            .mark_confirmed(id, String::from("block"))
+            .await
+            .unwrap();
+        assert!(repository.find_all_pending().await.unwrap().is_empty());
+        let confirmed = repository
+            .find_by_block_hash(String::from("block"))
+            .await
+            .unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].status, TransactionStatus::Confirmed);
+        assert_eq!(
+            repository
+                .find_by_address(String::from("receiver"))
+                .await
+                .unwrap(),
+            confirmed
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+use mongodb::{ClientSession, Database};
+use tracing::error;
+
+pub type BoxedSession = Box<dyn RepositorySession + Send>;
+pub type SharedSessionFactory = Arc<dyn SessionFactory + Send + Sync>;
+
+/// A unit of work spanning several repositories. Every write made through a
+/// `*_with_session` repository method is committed or aborted together.
+/// Committing a committed session again succeeds without writing anything,
+/// as the Mongo driver retries the commit, any other call on a finished
+/// session fails.
+#[async_trait]
+pub trait RepositorySession {
+    async fn commit(&mut self) -> Result<(), String>;
+    async fn abort(&mut self) -> Result<(), String>;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+#[async_trait]
+pub trait SessionFactory {
+    /// Starts a new session with a transaction already open.
+    async fn start(&self) -> Result<BoxedSession, String>;
+}
+
+pub struct MongoSession {
+    session: ClientSession,
+}
+
+#[async_trait]
+impl RepositorySession for MongoSession {
+    async fn commit(&mut self) -> Result<(), String> {
+        return self.session.commit_transaction().await.map_err(|e| {
+            error!("commit transaction error: {}", e);
+            e.to_string()
+        });
+    }
+
+    async fn abort(&mut self) -> Result<(), String> {
+        return self.session.abort_transaction().await.map_err(|e| {
+            error!("abort transaction error: {}", e);
+            e.to_string()
+        });
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
+}
+
+/// Returns the underlying mongodb session, failing when a session from
+/// another backend is handed to a Mongo repository.
+pub fn mongo_session(session: &mut BoxedSession) -> Result<&mut ClientSession, String> {
+    return match session.as_any_mut().downcast_mut::<MongoSession>() {
+        Some(s) => Ok(&mut s.session),
+        None => {
+            error!("session is not a mongodb session");
+            Err("session is not a mongodb session".to_string())
+        }
+    };
+}
+
+pub struct MongoSessionFactory {
+    db: Database,
+}
+
+impl MongoSessionFactory {
+    pub fn creation(db: Database) -> SharedSessionFactory {
+        return Arc::new(Self { db });
+    }
+}
+
+#[async_trait]
+impl SessionFactory for MongoSessionFactory {
+    async fn start(&self) -> Result<BoxedSession, String> {
+        let mut session = self.db.client().start_session().await.map_err(|e| {
+            error!("start session error: {}", e);
+            e.to_string()
+        })?;
+
+        session.start_transaction().await.map_err(|e| {
+            error!("start transaction error: {}", e);
+            e.to_string()
+        })?;
+
+        return Ok(Box::new(MongoSession { session }));
+    }
+}
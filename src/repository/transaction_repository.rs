@@ -3,9 +3,18 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use bson::{Document, doc, from_document, oid::ObjectId, to_bson};
 use mockall::automock;
-use mongodb::Database;
+use mongodb::{ClientSession, Database};
+use redb::WriteTransaction;
 use tracing::error;
 
+use super::{
+    memory_store::{MemoryState, MemoryStore, insert_document},
+    redb_store::{
+        self, RedbRead, RedbStore, TRANSACTIONS_BY_BLOCK_HASH, TRANSACTIONS_BY_FROM,
+        TRANSACTIONS_BY_STATUS, TRANSACTIONS_BY_TO, replace_document, status_name,
+    },
+    session::{BoxedSession, mongo_session},
+};
 use crate::entities::transaction_entity::{TransactionEntity, TransactionStatus};
 
 pub type SharedTransactionRepository = Arc<dyn TransactionRepository + Send + Sync>;
@@ -16,12 +25,37 @@ pub trait TransactionRepository {
     async fn find_by_id(&self, id: ObjectId) -> Result<Option<TransactionEntity>, String>;
     async fn find_by_address(&self, address: String) -> Result<Vec<TransactionEntity>, String>;
     async fn find_all_pending(&self) -> Result<Vec<TransactionEntity>, String>;
+    async fn find_pending_by_sender(&self, from: String) -> Result<Vec<TransactionEntity>, String>;
+    async fn find_by_block_hash(
+        &self,
+        block_hash: String,
+    ) -> Result<Vec<TransactionEntity>, String>;
+    /// Marks pending transactions submitted before `submitted_before`, or
+    /// whose `valid_until` has passed `now`, as expired and returns how many.
+    async fn expire_pending(&self, submitted_before: i64, now: i64) -> Result<u64, String>;
 
     async fn insert(&self, tx: TransactionEntity) -> Result<ObjectId, String>;
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx: TransactionEntity,
+    ) -> Result<ObjectId, String>;
 
     async fn update_status(&self, tx_id: ObjectId, status: TransactionStatus)
     -> Result<(), String>;
     async fn mark_confirmed(&self, tx_id: ObjectId, block_hash: String) -> Result<(), String>;
+    async fn update_status_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        status: TransactionStatus,
+    ) -> Result<(), String>;
+    async fn mark_confirmed_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        block_hash: String,
+    ) -> Result<(), String>;
 }
 
 pub struct MongoTransactionRepository {
@@ -32,6 +66,130 @@ impl MongoTransactionRepository {
     pub fn creation(db: Database) -> SharedTransactionRepository {
         return Arc::new(Self { db });
     }
+
+    async fn update_one(
+        &self,
+        session: Option<&mut ClientSession>,
+        tx_id: ObjectId,
+        update: Document,
+    ) -> Result<(), String> {
+        let filter = doc! { "_id": tx_id };
+
+        let collection = self.db.collection::<Document>("transactions");
+        let action = collection.update_one(filter, update);
+        let result = match session {
+            Some(session) => action.session(session).await,
+            None => action.await,
+        }
+        .map_err(|e| e.to_string())?;
+
+        if result.matched_count == 0 {
+            return Err("Transaction not found".to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn insert_one(
+        &self,
+        session: Option<&mut ClientSession>,
+        tx: TransactionEntity,
+    ) -> Result<ObjectId, String> {
+        let mut doc = doc! {
+            "hash": tx.hash,
+            "block_hash": tx.block_hash,
+            "from": tx.from,
+            "to": tx.to,
+            "amount": tx.amount as i64,
+            "fee": tx.fee as i64,
+            "nonce": tx.nonce as i64,
+            "valid_until": tx.valid_until,
+            "scheme": to_bson(&tx.scheme).map_err(|e| e.to_string())?,
+            "public_key": tx.public_key,
+            "signature": tx.signature,
+            "timestamp": tx.timestamp,
+            "status": to_bson(&tx.status).map_err(|e| e.to_string())?,
+        };
+        if let Some(id) = tx.id {
+            doc.insert("_id", id);
+        }
+
+        let collection = self.db.collection::<Document>("transactions");
+        let action = collection.insert_one(doc);
+        let inserted_object_id = match session {
+            Some(session) => action.session(session).await,
+            None => action.await,
+        }
+        .map_err(|e| {
+            error!("insert a new transaction failed: {}", e);
+            return e.to_string();
+        })?
+        .inserted_id
+        .as_object_id();
+
+        let object_id = match inserted_object_id {
+            Some(id) => id,
+            None => {
+                error!("issue with new _id");
+                return Err(String::new());
+            }
+        };
+
+        return Ok(object_id);
+    }
+
+    async fn find_many(
+        &self,
+        filter: Document,
+        sort: Document,
+    ) -> Result<Vec<TransactionEntity>, String> {
+        let mut cursor = self
+            .db
+            .collection::<Document>("transactions")
+            .find(filter)
+            .sort(sort)
+            .await
+            .map_err(|e| {
+                error!("find tx error: {}", e);
+                return e.to_string();
+            })?;
+
+        let mut txs = Vec::new();
+        while cursor.advance().await.map_err(|e| {
+            error!("find tx error: {}", e);
+            return e.to_string();
+        })? {
+            let tx = from_document(cursor.deserialize_current().map_err(|e| {
+                error!("failed to deserialize: {}", e);
+                return e.to_string();
+            })?)
+            .map_err(|e| {
+                error!("convert doc to TransactionEntity failed: {}", e);
+                return e.to_string();
+            })?;
+
+            txs.push(tx);
+        }
+
+        return Ok(txs);
+    }
+
+    fn status_update(status: TransactionStatus) -> Result<Document, String> {
+        return Ok(doc! {
+            "$set": {
+                "status": to_bson(&status).map_err(|e| e.to_string())?
+            }
+        });
+    }
+
+    fn confirmed_update(block_hash: String) -> Result<Document, String> {
+        return Ok(doc! {
+            "$set": {
+                "status": to_bson(&TransactionStatus::Confirmed).map_err(|e| e.to_string())?,
+                "block_hash": block_hash,
+            }
+        });
+    }
 }
 
 #[async_trait]
@@ -103,67 +261,219 @@ impl TransactionRepository for MongoTransactionRepository {
     }
 
     async fn find_all_pending(&self) -> Result<Vec<TransactionEntity>, String> {
-        let filter = doc! { "status": "pending"};
-        let mut cursor = self
+        return self
+            .find_many(doc! { "status": "pending" }, doc! { "timestamp": 1 })
+            .await;
+    }
+
+    async fn find_pending_by_sender(&self, from: String) -> Result<Vec<TransactionEntity>, String> {
+        return self
+            .find_many(
+                doc! { "status": "pending", "from": from },
+                doc! { "nonce": 1 },
+            )
+            .await;
+    }
+
+    async fn find_by_block_hash(
+        &self,
+        block_hash: String,
+    ) -> Result<Vec<TransactionEntity>, String> {
+        return self
+            .find_many(doc! { "block_hash": block_hash }, doc! { "timestamp": 1 })
+            .await;
+    }
+
+    async fn expire_pending(&self, submitted_before: i64, now: i64) -> Result<u64, String> {
+        let filter = doc! {
+            "status": "pending",
+            "$or": [
+                { "timestamp": { "$lt": submitted_before } },
+                { "valid_until": { "$ne": null, "$lt": now } }
+            ]
+        };
+        let update = Self::status_update(TransactionStatus::Expired)?;
+
+        let result = self
             .db
             .collection::<Document>("transactions")
-            .find(filter)
-            .sort(doc! {"timestamp": 1})
+            .update_many(filter, update)
             .await
             .map_err(|e| {
-                error!("find tx status pending error: {}", e);
+                error!("expire pending transactions error: {}", e);
                 return e.to_string();
             })?;
 
-        let mut txs = Vec::new();
-        while cursor.advance().await.map_err(|e| {
-            error!("find tx status pending error: {}", e);
-            return e.to_string();
-        })? {
-            let tx = from_document(cursor.deserialize_current().map_err(|e| {
-                error!("failed to deserialize: {}", e);
-                return e.to_string();
-            })?)
-            .map_err(|e| {
-                error!("convert doc to TransactionEntity failed: {}", e);
-                return e.to_string();
-            })?;
+        return Ok(result.modified_count);
+    }
 
-            txs.push(tx);
-        }
+    async fn insert(&self, tx: TransactionEntity) -> Result<ObjectId, String> {
+        return self.insert_one(None, tx).await;
+    }
 
-        return Ok(txs);
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx: TransactionEntity,
+    ) -> Result<ObjectId, String> {
+        let session = mongo_session(session)?;
+        return self.insert_one(Some(session), tx).await;
     }
 
-    async fn insert(&self, tx: TransactionEntity) -> Result<ObjectId, String> {
-        let inserted_object_id = self
-            .db
-            .collection::<Document>("transactions")
-            .insert_one(doc! {
-                "from": tx.from,
-                "to": tx.to,
-                "amount": tx.amount as i64,
-                "signature": tx.signature,
-                "timestamp": tx.timestamp,
-                "status": to_bson(&tx.status).unwrap(),
-            })
-            .await
-            .map_err(|e| {
-                error!("insert a new transaction failed: {}", e);
-                return e.to_string();
-            })?
-            .inserted_id
-            .as_object_id();
+    async fn update_status(
+        &self,
+        tx_id: ObjectId,
+        status: TransactionStatus,
+    ) -> Result<(), String> {
+        let update = Self::status_update(status)?;
+        return self.update_one(None, tx_id, update).await;
+    }
 
-        let object_id = match inserted_object_id {
-            Some(id) => id,
-            None => {
-                error!("issue with new _id");
-                return Err(String::new());
+    async fn mark_confirmed(&self, tx_id: ObjectId, block_hash: String) -> Result<(), String> {
+        let update = Self::confirmed_update(block_hash)?;
+        return self.update_one(None, tx_id, update).await;
+    }
+
+    async fn update_status_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        status: TransactionStatus,
+    ) -> Result<(), String> {
+        let update = Self::status_update(status)?;
+        let session = mongo_session(session)?;
+        return self.update_one(Some(session), tx_id, update).await;
+    }
+
+    async fn mark_confirmed_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        block_hash: String,
+    ) -> Result<(), String> {
+        let update = Self::confirmed_update(block_hash)?;
+        let session = mongo_session(session)?;
+        return self.update_one(Some(session), tx_id, update).await;
+    }
+}
+
+pub struct MemoryTransactionRepository {
+    store: Arc<MemoryStore>,
+}
+
+impl MemoryTransactionRepository {
+    pub fn creation(store: Arc<MemoryStore>) -> SharedTransactionRepository {
+        return Arc::new(Self { store });
+    }
+
+    /// Matching transactions in insertion order, stably sorted by `key` like
+    /// a Mongo sort.
+    fn find_many<K: Ord>(
+        &self,
+        filter: impl Fn(&TransactionEntity) -> bool,
+        key: impl Fn(&TransactionEntity) -> K,
+    ) -> Vec<TransactionEntity> {
+        let mut txs: Vec<TransactionEntity> = self.store.read(|state| {
+            state
+                .transactions
+                .values()
+                .filter(|tx| filter(tx))
+                .cloned()
+                .collect()
+        });
+        txs.sort_by_key(key);
+        return txs;
+    }
+
+    fn insert_one(state: &mut MemoryState, mut tx: TransactionEntity) -> Result<ObjectId, String> {
+        let id = tx.id.unwrap_or_default();
+        tx.id = Some(id);
+        return insert_document(&mut state.transactions, id, tx);
+    }
+
+    fn update_one(
+        state: &mut MemoryState,
+        tx_id: ObjectId,
+        update: impl FnOnce(&mut TransactionEntity),
+    ) -> Result<(), String> {
+        return match state.transactions.get_mut(&tx_id) {
+            Some(tx) => {
+                update(tx);
+                Ok(())
             }
+            None => Err("Transaction not found".to_string()),
         };
+    }
 
-        return Ok(object_id);
+    fn confirm(tx: &mut TransactionEntity, block_hash: String) {
+        tx.status = TransactionStatus::Confirmed;
+        tx.block_hash = Some(block_hash);
+    }
+}
+
+#[async_trait]
+impl TransactionRepository for MemoryTransactionRepository {
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<TransactionEntity>, String> {
+        return Ok(self
+            .store
+            .read(|state| state.transactions.get(&id).cloned()));
+    }
+
+    async fn find_by_address(&self, address: String) -> Result<Vec<TransactionEntity>, String> {
+        return Ok(self.find_many(|tx| tx.from == address || tx.to == address, |_| ()));
+    }
+
+    async fn find_all_pending(&self) -> Result<Vec<TransactionEntity>, String> {
+        return Ok(self.find_many(
+            |tx| tx.status == TransactionStatus::Pending,
+            |tx| tx.timestamp,
+        ));
+    }
+
+    async fn find_pending_by_sender(&self, from: String) -> Result<Vec<TransactionEntity>, String> {
+        return Ok(self.find_many(
+            |tx| tx.status == TransactionStatus::Pending && tx.from == from,
+            |tx| tx.nonce,
+        ));
+    }
+
+    async fn find_by_block_hash(
+        &self,
+        block_hash: String,
+    ) -> Result<Vec<TransactionEntity>, String> {
+        return Ok(self.find_many(
+            |tx| tx.block_hash.as_ref() == Some(&block_hash),
+            |tx| tx.timestamp,
+        ));
+    }
+
+    async fn expire_pending(&self, submitted_before: i64, now: i64) -> Result<u64, String> {
+        return Ok(self.store.write(|state| {
+            let mut expired = 0;
+            for tx in state.transactions.values_mut() {
+                if tx.status == TransactionStatus::Pending
+                    && (tx.timestamp < submitted_before
+                        || tx.valid_until.is_some_and(|valid_until| valid_until < now))
+                {
+                    tx.status = TransactionStatus::Expired;
+                    expired += 1;
+                }
+            }
+            expired
+        }));
+    }
+
+    async fn insert(&self, tx: TransactionEntity) -> Result<ObjectId, String> {
+        return self.store.write(|state| Self::insert_one(state, tx));
+    }
+
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx: TransactionEntity,
+    ) -> Result<ObjectId, String> {
+        let state = self.store.session_state(session)?;
+        return Self::insert_one(state, tx);
     }
 
     async fn update_status(
@@ -171,48 +481,204 @@ impl TransactionRepository for MongoTransactionRepository {
         tx_id: ObjectId,
         status: TransactionStatus,
     ) -> Result<(), String> {
-        let filter = doc! { "_id": tx_id };
-        let update = doc! {
-            "$set": {
-                "status": to_bson(&status).map_err(|e| e.to_string())?
-            }
-        };
+        return self
+            .store
+            .write(|state| Self::update_one(state, tx_id, |tx| tx.status = status));
+    }
 
-        let result = self
-            .db
-            .collection::<Document>("transactions")
-            .update_one(filter, update)
-            .await
-            .map_err(|e| e.to_string())?;
+    async fn mark_confirmed(&self, tx_id: ObjectId, block_hash: String) -> Result<(), String> {
+        return self
+            .store
+            .write(|state| Self::update_one(state, tx_id, |tx| Self::confirm(tx, block_hash)));
+    }
 
-        if result.matched_count == 0 {
-            return Err("Transaction not found".to_string());
-        }
+    async fn update_status_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        status: TransactionStatus,
+    ) -> Result<(), String> {
+        let state = self.store.session_state(session)?;
+        return Self::update_one(state, tx_id, |tx| tx.status = status);
+    }
 
-        Ok(())
+    async fn mark_confirmed_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        block_hash: String,
+    ) -> Result<(), String> {
+        let state = self.store.session_state(session)?;
+        return Self::update_one(state, tx_id, |tx| Self::confirm(tx, block_hash));
     }
+}
 
-    async fn mark_confirmed(&self, tx_id: ObjectId, block_hash: String) -> Result<(), String> {
-        let filter = doc! { "_id": tx_id };
+pub struct RedbTransactionRepository {
+    store: Arc<RedbStore>,
+}
 
-        let update = doc! {
-            "$set": {
-                "status": to_bson(&TransactionStatus::Confirmed).map_err(|e| e.to_string())?,
-                "block_hash": block_hash,
-            }
-        };
+impl RedbTransactionRepository {
+    pub fn creation(store: Arc<RedbStore>) -> SharedTransactionRepository {
+        return Arc::new(Self { store });
+    }
 
-        let result = self
-            .db
-            .collection::<Document>("transactions")
-            .update_one(filter, update)
-            .await
-            .map_err(|e| e.to_string())?;
+    fn pending(txn: &impl RedbRead) -> Result<Vec<TransactionEntity>, String> {
+        return txn.find_by(
+            TRANSACTIONS_BY_STATUS,
+            status_name(&TransactionStatus::Pending).as_bytes(),
+        );
+    }
 
-        if result.matched_count == 0 {
+    fn insert_one(txn: &WriteTransaction, mut tx: TransactionEntity) -> Result<ObjectId, String> {
+        tx.id = Some(tx.id.unwrap_or_default());
+        return redb_store::insert_document(txn, &tx);
+    }
+
+    fn update_one(
+        txn: &WriteTransaction,
+        tx_id: ObjectId,
+        update: impl FnOnce(&mut TransactionEntity),
+    ) -> Result<(), String> {
+        let Some(mut tx) = txn.document(&tx_id)? else {
             return Err("Transaction not found".to_string());
-        }
+        };
+        update(&mut tx);
+        return replace_document(txn, &tx);
+    }
 
-        Ok(())
+    fn confirm(tx: &mut TransactionEntity, block_hash: String) {
+        tx.status = TransactionStatus::Confirmed;
+        tx.block_hash = Some(block_hash);
+    }
+}
+
+#[async_trait]
+impl TransactionRepository for RedbTransactionRepository {
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<TransactionEntity>, String> {
+        return self.store.read(move |txn| txn.document(&id)).await;
+    }
+
+    async fn find_by_address(&self, address: String) -> Result<Vec<TransactionEntity>, String> {
+        return self
+            .store
+            .read(move |txn| {
+                let mut ids = txn.index_ids(TRANSACTIONS_BY_FROM, address.as_bytes())?;
+                ids.extend(txn.index_ids(TRANSACTIONS_BY_TO, address.as_bytes())?);
+                ids.sort();
+                ids.dedup();
+                return txn.documents(ids);
+            })
+            .await;
+    }
+
+    async fn find_all_pending(&self) -> Result<Vec<TransactionEntity>, String> {
+        return self
+            .store
+            .read(|txn| {
+                let mut txs = Self::pending(txn)?;
+                txs.sort_by_key(|tx| tx.timestamp);
+                return Ok(txs);
+            })
+            .await;
+    }
+
+    async fn find_pending_by_sender(&self, from: String) -> Result<Vec<TransactionEntity>, String> {
+        return self
+            .store
+            .read(move |txn| {
+                let mut txs: Vec<TransactionEntity> = txn
+                    .find_by(TRANSACTIONS_BY_FROM, from.as_bytes())?
+                    .into_iter()
+                    .filter(|tx: &TransactionEntity| tx.status == TransactionStatus::Pending)
+                    .collect();
+                txs.sort_by_key(|tx| tx.nonce);
+                return Ok(txs);
+            })
+            .await;
+    }
+
+    async fn find_by_block_hash(
+        &self,
+        block_hash: String,
+    ) -> Result<Vec<TransactionEntity>, String> {
+        return self
+            .store
+            .read(move |txn| {
+                let mut txs: Vec<TransactionEntity> =
+                    txn.find_by(TRANSACTIONS_BY_BLOCK_HASH, block_hash.as_bytes())?;
+                txs.sort_by_key(|tx| tx.timestamp);
+                return Ok(txs);
+            })
+            .await;
+    }
+
+    async fn expire_pending(&self, submitted_before: i64, now: i64) -> Result<u64, String> {
+        return self
+            .store
+            .write(move |txn| {
+                let mut expired = 0;
+                for mut tx in Self::pending(txn)? {
+                    if tx.timestamp < submitted_before
+                        || tx.valid_until.is_some_and(|valid_until| valid_until < now)
+                    {
+                        tx.status = TransactionStatus::Expired;
+                        replace_document(txn, &tx)?;
+                        expired += 1;
+                    }
+                }
+                return Ok(expired);
+            })
+            .await;
+    }
+
+    async fn insert(&self, tx: TransactionEntity) -> Result<ObjectId, String> {
+        return self.store.write(move |txn| Self::insert_one(txn, tx)).await;
+    }
+
+    async fn insert_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx: TransactionEntity,
+    ) -> Result<ObjectId, String> {
+        let txn = self.store.session_txn(session)?;
+        return Self::insert_one(txn, tx);
+    }
+
+    async fn update_status(
+        &self,
+        tx_id: ObjectId,
+        status: TransactionStatus,
+    ) -> Result<(), String> {
+        return self
+            .store
+            .write(move |txn| Self::update_one(txn, tx_id, |tx| tx.status = status))
+            .await;
+    }
+
+    async fn mark_confirmed(&self, tx_id: ObjectId, block_hash: String) -> Result<(), String> {
+        return self
+            .store
+            .write(move |txn| Self::update_one(txn, tx_id, |tx| Self::confirm(tx, block_hash)))
+            .await;
+    }
+
+    async fn update_status_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        status: TransactionStatus,
+    ) -> Result<(), String> {
+        let txn = self.store.session_txn(session)?;
+        return Self::update_one(txn, tx_id, |tx| tx.status = status);
+    }
+
+    async fn mark_confirmed_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        block_hash: String,
+    ) -> Result<(), String> {
+        let txn = self.store.session_txn(session)?;
+        return Self::update_one(txn, tx_id, |tx| Self::confirm(tx, block_hash));
     }
 }
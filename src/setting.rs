@@ -2,11 +2,21 @@ use std::sync::Arc;
 
 use config::Config;
 
+use crate::{crypto_helper, pow_helper};
+
 #[derive(Debug, Clone)]
 pub struct Server {
     pub port: i64,
 }
 
+#[derive(Debug, Clone)]
+pub struct Development {
+    pub generate_keys: bool,
+    /// Serves `PATCH /addresses/{address}`, which credits coins outside of
+    /// any block.
+    pub faucet: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Database {
     pub host: String,
@@ -14,12 +24,69 @@ pub struct Database {
     pub username: String,
     pub password: String,
     pub dbname: String,
+    /// Sets `$jsonSchema` validators on the collections at startup.
+    pub schema_validation: bool,
+    /// Applies pending migrations at startup instead of refusing to start.
+    pub migrate_on_startup: bool,
+}
+
+/// Where repositories keep their data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageBackend {
+    Mongo,
+    /// Lost on restart, for tests and local development.
+    Memory,
+    /// Embedded database in the file at `storage.path`.
+    Redb,
+}
+
+#[derive(Debug, Clone)]
+pub struct Storage {
+    pub backend: StorageBackend,
+    pub path: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub id: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub create_unknown_receiver: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mining {
+    pub difficulty: u32,
+    pub retarget_interval: u64,
+    pub target_block_time: u64,
+    pub max_adjustment_factor: u64,
+    /// Credited with block rewards and fees, blocks are not built until
+    /// an operator sets it.
+    pub miner_address: Option<String>,
+    pub block_reward: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    pub max_transactions: u64,
+    pub max_block_transactions: u64,
+    pub max_block_bytes: u64,
+    pub transaction_ttl: i64,
+    pub sweep_interval: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct Setting {
     pub server: Server,
+    pub development: Development,
     pub database: Database,
+    pub storage: Storage,
+    pub chain: Chain,
+    pub transaction: Transaction,
+    pub mining: Mining,
+    pub mempool: Mempool,
 }
 
 impl Setting {
@@ -29,23 +96,105 @@ impl Setting {
             .build()
             .unwrap();
 
+        return Self::from_config(settings);
+    }
+
+    /// Loads settings from TOML text, used by tests with `Settings.toml`.
+    pub fn from_toml(toml: &str) -> Result<Arc<Setting>, config::ConfigError> {
+        let settings = Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()?;
+
+        return Self::from_config(settings);
+    }
+
+    fn from_config(settings: Config) -> Result<Arc<Setting>, config::ConfigError> {
+        let backend = match settings.get_string("storage.backend").unwrap().as_str() {
+            "mongo" => StorageBackend::Mongo,
+            "memory" => StorageBackend::Memory,
+            "redb" => StorageBackend::Redb,
+            other => {
+                return Err(config::ConfigError::Message(format!(
+                    "unknown storage.backend {}, expected mongo, memory or redb",
+                    other
+                )));
+            }
+        };
+
+        let difficulty = settings.get_int("mining.difficulty").unwrap();
+        if !(1..=pow_helper::MAX_DIFFICULTY as i64).contains(&difficulty) {
+            return Err(config::ConfigError::Message(format!(
+                "mining.difficulty {} must be between 1 and {}",
+                difficulty,
+                pow_helper::MAX_DIFFICULTY
+            )));
+        }
+
+        let miner_address = match settings.get_string("mining.miner_address").unwrap() {
+            address if address.is_empty() => None,
+            address => match crypto_helper::parse_address(&address) {
+                Ok(_) => Some(address),
+                Err(e) => {
+                    return Err(config::ConfigError::Message(format!(
+                        "mining.miner_address {} is not a valid address: {}",
+                        address, e
+                    )));
+                }
+            },
+        };
+
         return Ok(Arc::new(Setting {
             server: Server {
                 port: settings.get_int("server.port").unwrap(),
             },
+            development: Development {
+                generate_keys: settings.get_bool("development.generate_keys").unwrap(),
+                faucet: settings.get_bool("development.faucet").unwrap(),
+            },
             database: Database {
                 host: settings.get_string("database.host").unwrap(),
                 port: settings.get_int("database.port").unwrap(),
                 username: settings.get_string("database.username").unwrap(),
                 password: settings.get_string("database.password").unwrap(),
                 dbname: settings.get_string("database.dbname").unwrap(),
+                schema_validation: settings.get_bool("database.schema_validation").unwrap(),
+                migrate_on_startup: settings.get_bool("database.migrate_on_startup").unwrap(),
+            },
+            storage: Storage {
+                backend,
+                path: settings.get_string("storage.path").unwrap(),
+            },
+            chain: Chain {
+                id: settings.get_int("chain.id").unwrap() as u64,
+            },
+            transaction: Transaction {
+                create_unknown_receiver: settings
+                    .get_bool("transaction.create_unknown_receiver")
+                    .unwrap(),
+            },
+            mining: Mining {
+                difficulty: difficulty as u32,
+                retarget_interval: settings.get_int("mining.retarget_interval").unwrap() as u64,
+                target_block_time: settings.get_int("mining.target_block_time").unwrap() as u64,
+                max_adjustment_factor: settings.get_int("mining.max_adjustment_factor").unwrap()
+                    as u64,
+                miner_address,
+                block_reward: settings.get_int("mining.block_reward").unwrap() as u64,
+            },
+            mempool: Mempool {
+                max_transactions: settings.get_int("mempool.max_transactions").unwrap() as u64,
+                max_block_transactions: settings.get_int("mempool.max_block_transactions").unwrap()
+                    as u64,
+                max_block_bytes: settings.get_int("mempool.max_block_bytes").unwrap() as u64,
+                transaction_ttl: settings.get_int("mempool.transaction_ttl").unwrap(),
+                sweep_interval: settings.get_int("mempool.sweep_interval").unwrap() as u64,
             },
         }));
     }
 
     pub fn get_db_url(&self) -> String {
         return format!(
-            "mongodb://{}:{}@{}:{}",
+            "mongodb://{}:{}@{}:{}/?directConnection=true",
             self.database.username, self.database.password, self.database.host, self.database.port
         );
     }
@@ -0,0 +1,47 @@
+use rayon::prelude::*;
+
+use crate::{
+    crypto_helper,
+    entities::transaction_entity::{COINBASE_ADDRESS, TransactionEntity},
+    models::block_model::SignatureFailure,
+};
+
+/// Checks one transaction: its public key must hash to `from` and its
+/// signature must verify under the scheme it declares.
+pub fn verify_transaction(tx: &TransactionEntity, chain_id: u64) -> Result<(), String> {
+    match crypto_helper::address_from_public_key(&tx.public_key) {
+        Ok(address) if address == tx.from => {}
+        Ok(_) => return Err(format!("public key does not belong to address {}", tx.from)),
+        Err(e) => return Err(format!("invalid public key: {}", e)),
+    };
+
+    return match crypto_helper::verify_signature(
+        &tx.payload(chain_id),
+        &tx.public_key,
+        &tx.signature,
+    ) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("invalid signature".to_string()),
+        Err(e) => Err(format!("malformed signature: {}", e)),
+    };
+}
+
+/// Verifies the signatures of `txs` in parallel on the rayon pool, all
+/// threads sharing `crypto_helper::SECP256K1`. Coinbase transactions are
+/// unsigned and skipped. Returns the failures in the order of `txs`.
+///
+/// CPU bound, async callers should run it on `spawn_blocking`.
+pub fn verify_transactions(txs: &[TransactionEntity], chain_id: u64) -> Vec<SignatureFailure> {
+    return txs
+        .par_iter()
+        .filter(|tx| tx.from != COINBASE_ADDRESS)
+        .filter_map(|tx| {
+            let error = verify_transaction(tx, chain_id).err()?;
+            return Some(SignatureFailure {
+                tx_id: tx.id.map(|id| id.to_hex()),
+                tx_hash: tx.hash.clone(),
+                error,
+            });
+        })
+        .collect();
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        crypto_helper::{address_from_public_key, public_key_from_secret_key, sign_payload},
+        entities::transaction_entity::{COINBASE_ADDRESS, TransactionEntity, TransactionStatus},
+        models::transaction_model::{SignatureScheme, TransactionPayload},
+        signature_helper::{verify_transaction, verify_transactions},
+        timer_helper::TimerHelper,
+    };
+
+    const CHAIN_ID: u64 = 1;
+
+    fn secret_key(n: u8) -> String {
+        let mut secret_key = [0u8; 32];
+        secret_key[31] = n;
+        return hex::encode(secret_key);
+    }
+
+    fn signed(n: u8, scheme: SignatureScheme) -> TransactionEntity {
+        let secret_key = secret_key(n);
+        let public_key = public_key_from_secret_key(&secret_key).unwrap();
+        let payload = TransactionPayload {
+            chain_id: CHAIN_ID,
+            from: address_from_public_key(&public_key).unwrap(),
+            to: String::from("RLkZJhcqT2ac31oqBeLvo6egULQR54LNxb"),
+            amount: n as u64,
+            fee: 1,
+            nonce: 0,
+            valid_until: None,
+            scheme,
+        };
+        let signature = sign_payload(&payload, &secret_key).unwrap();
+        return TransactionEntity::new(
+            &payload,
+            public_key,
+            signature,
+            TransactionStatus::Pending,
+            TimerHelper::Mock.creation(),
+        );
+    }
+
+    #[test]
+    fn verify_transactions_valid_batch_test() {
+        let txs: Vec<TransactionEntity> = (1..=16)
+            .map(|n| match n % 2 {
+                0 => signed(n, SignatureScheme::Ecdsa),
+                _ => signed(n, SignatureScheme::Schnorr),
+            })
+            .collect();
+
+        assert!(verify_transactions(&txs, CHAIN_ID).is_empty());
+    }
+
+    #[test]
+    fn verify_transactions_reports_failures_test() {
+        let mut tampered = signed(2, SignatureScheme::Ecdsa);
+        tampered.amount += 1;
+        let mut foreign_key = signed(3, SignatureScheme::Schnorr);
+        foreign_key.public_key = signed(4, SignatureScheme::Schnorr).public_key;
+        let mut coinbase = signed(5, SignatureScheme::Ecdsa);
+        coinbase.from = String::from(COINBASE_ADDRESS);
+        coinbase.signature = String::new();
+
+        let txs = vec![
+            signed(1, SignatureScheme::Ecdsa),
+            tampered.clone(),
+            coinbase,
+            foreign_key.clone(),
+            signed(6, SignatureScheme::Schnorr),
+        ];
+        let failures = verify_transactions(&txs, CHAIN_ID);
+
+        let hashes: Vec<&str> = failures.iter().map(|f| f.tx_hash.as_str()).collect();
+        assert_eq!(
+            hashes,
+            vec![tampered.hash.as_str(), foreign_key.hash.as_str()]
+        );
+        assert_eq!(failures[0].error, "invalid signature");
+    }
+
+    #[test]
+    fn verify_transaction_other_chain_test() {
+        let tx = signed(7, SignatureScheme::Schnorr);
+
+        assert!(verify_transaction(&tx, CHAIN_ID).is_ok());
+        assert!(verify_transaction(&tx, CHAIN_ID + 1).is_err());
+    }
+}
@@ -4,45 +4,113 @@ mod tests {
 
     use bson::oid::ObjectId;
     use mockall::predicate::eq;
+    use secp256k1::rand::rngs::OsRng;
 
     use crate::{
-        entities::address_entity::AddressEntity, models::address_model::InsertAddress,
-        repository::address_repository::MockAddressRepository, timer_helper::TimerHelper,
+        crypto_helper::{SECP256K1, address_from_public_key, sign_registration},
+        entities::address_entity::AddressEntity,
+        models::address_model::InsertAddress,
+        repository::address_repository::MockAddressRepository,
+        setting::Setting,
+        timer_helper::TimerHelper,
         usecases::address_usecase::AddressUsecase,
     };
 
+    fn registration(chain_id: u64) -> InsertAddress {
+        let (secret_key, public_key) = SECP256K1.generate_keypair(&mut OsRng);
+        let public_key = public_key.to_string();
+        let signature = sign_registration(
+            chain_id,
+            &public_key,
+            &hex::encode(secret_key.secret_bytes()),
+        )
+        .unwrap();
+        return InsertAddress {
+            public_key,
+            signature,
+        };
+    }
+
+    fn usecase(address_repository_mock: MockAddressRepository) -> Arc<AddressUsecase> {
+        return AddressUsecase::creation(
+            Arc::new(address_repository_mock),
+            Setting::from_toml(include_str!("../../Settings.toml")).unwrap(),
+            TimerHelper::Mock.creation(),
+        );
+    }
+
     #[tokio::test]
     async fn create_address_test() {
         let mut address_repository_mock = MockAddressRepository::new();
         let timer_helper = TimerHelper::Mock.creation();
 
-        let req = InsertAddress {
-            public_key: String::from("test_public_key"),
-        };
+        let req = registration(1);
+        let address = address_from_public_key(&req.public_key).unwrap();
         let expected_id = ObjectId::parse_str("000000000000000000000001").unwrap();
 
         address_repository_mock
             .expect_get_by_address()
-            .with(eq(req.public_key.clone()))
+            .with(eq(address.clone()))
             .times(1)
             .returning(|_| Box::pin(async { Err(String::new()) }));
 
         address_repository_mock
             .expect_insert()
             .with(eq(AddressEntity::new(
-                req.public_key.clone(),
+                address.clone(),
+                Some(req.public_key.clone()),
                 Arc::clone(&timer_helper),
             )))
-            .returning(move |_| Box::pin(async move { Ok(expected_id.clone()) }));
-
-        let address_usecase =
-            AddressUsecase::creation(Arc::new(address_repository_mock), timer_helper);
+            .returning(move |_| Box::pin(async move { Ok(expected_id) }));
 
-        let result = match address_usecase.create_new_address(req).await {
+        let result = match usecase(address_repository_mock)
+            .create_new_address(req)
+            .await
+        {
             Ok(r) => r,
             Err(_) => panic!("create new address error"),
         };
 
-        assert_eq!(result, expected_id);
+        assert_eq!(result.id, Some(expected_id));
+        assert_eq!(result.address, address);
+    }
+
+    #[tokio::test]
+    async fn create_address_other_chain_proof_test() {
+        let req = registration(2);
+        let public_key = req.public_key.clone();
+
+        let result = usecase(MockAddressRepository::new())
+            .create_new_address(req)
+            .await;
+
+        let error = match result {
+            Ok(_) => panic!("proof for another chain accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(
+            error,
+            format!("invalid signature for address: {}", public_key)
+        );
+    }
+
+    #[tokio::test]
+    async fn create_address_foreign_key_proof_test() {
+        let mut req = registration(1);
+        req.signature = registration(1).signature;
+        let public_key = req.public_key.clone();
+
+        let result = usecase(MockAddressRepository::new())
+            .create_new_address(req)
+            .await;
+
+        let error = match result {
+            Ok(_) => panic!("proof signed by another key accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(
+            error,
+            format!("invalid signature for address: {}", public_key)
+        );
     }
 }
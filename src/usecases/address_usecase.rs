@@ -1,26 +1,31 @@
 use std::sync::Arc;
 
 use crate::{
+    crypto_helper,
     entities::address_entity::AddressEntity,
     errors::{address_error::APIAddressError, error::IntoErrorResponse},
     models::address_model::{CoinWithAddress, InsertAddress},
-    repository::address_repository::SharedAddressRepository,
+    repository::{address_repository::SharedAddressRepository, session::BoxedSession},
+    setting::Setting,
     timer_helper::IntoTimerHelperShared,
 };
-use bson::oid::ObjectId;
+use secp256k1::rand::rngs::OsRng;
 
 pub struct AddressUsecase {
     address_repository: SharedAddressRepository,
+    setting: Arc<Setting>,
     timer_helper: IntoTimerHelperShared,
 }
 
 impl AddressUsecase {
     pub fn creation(
         address_repository: SharedAddressRepository,
+        setting: Arc<Setting>,
         timer_helper: IntoTimerHelperShared,
     ) -> Arc<Self> {
         return Arc::new(Self {
             address_repository,
+            setting,
             timer_helper,
         });
     }
@@ -28,33 +33,83 @@ impl AddressUsecase {
     pub async fn create_new_address(
         &self,
         insert_address: InsertAddress,
-    ) -> Result<ObjectId, Box<dyn IntoErrorResponse>> {
-        match self
-            .address_repository
-            .get_by_address(insert_address.public_key.clone())
-            .await
-        {
-            Ok(Some(_)) => {
-                return Err(Box::new(APIAddressError::AddressAlreadyExists(
+    ) -> Result<AddressEntity, Box<dyn IntoErrorResponse>> {
+        if !crypto_helper::is_compressed_public_key(&insert_address.public_key) {
+            return Err(Box::new(APIAddressError::InvalidPublicKey(
+                insert_address.public_key,
+            )));
+        }
+
+        let is_valid = crypto_helper::verify_registration(
+            self.setting.chain.id,
+            &insert_address.public_key,
+            &insert_address.signature,
+        )
+        .unwrap_or(false);
+        if !is_valid {
+            return Err(Box::new(APIAddressError::InvaidSignature(
+                insert_address.public_key,
+            )));
+        }
+
+        let address = match crypto_helper::address_from_public_key(&insert_address.public_key) {
+            Ok(address) => address,
+            Err(_) => {
+                return Err(Box::new(APIAddressError::InvalidPublicKey(
                     insert_address.public_key,
                 )));
             }
-            _ => {}
         };
 
-        return match self
+        if let Ok(Some(_)) = self
             .address_repository
-            .insert(AddressEntity::new(
-                insert_address.public_key,
-                Arc::clone(&self.timer_helper),
-            ))
+            .get_by_address(address.clone())
             .await
         {
-            Ok(id) => Ok(id),
+            return Err(Box::new(APIAddressError::AddressAlreadyExists(address)));
+        }
+
+        let mut new_address = AddressEntity::new(
+            address,
+            Some(insert_address.public_key),
+            Arc::clone(&self.timer_helper),
+        );
+        return match self.address_repository.insert(new_address.clone()).await {
+            Ok(id) => {
+                new_address.id = Some(id);
+                Ok(new_address)
+            }
             Err(e) => Err(Box::new(APIAddressError::GenerateAddressError(e))),
         };
     }
 
+    /// Generates a keypair on the node and registers it, returning the new
+    /// address and its secret key. Development only, the node sees the
+    /// secret key.
+    pub async fn generate_address(
+        &self,
+    ) -> Result<(AddressEntity, String), Box<dyn IntoErrorResponse>> {
+        let (secret_key, public_key) = crypto_helper::SECP256K1.generate_keypair(&mut OsRng);
+        let public_key = public_key.to_string();
+        let secret_key = hex::encode(secret_key.secret_bytes());
+
+        let signature =
+            match crypto_helper::sign_registration(self.setting.chain.id, &public_key, &secret_key)
+            {
+                Ok(signature) => signature,
+                Err(e) => return Err(Box::new(APIAddressError::SignError(e.to_string()))),
+            };
+
+        let address = self
+            .create_new_address(InsertAddress {
+                public_key,
+                signature,
+            })
+            .await?;
+
+        return Ok((address, secret_key));
+    }
+
     pub async fn deposit_coin(
         &self,
         coin_with_address: CoinWithAddress,
@@ -62,7 +117,11 @@ impl AddressUsecase {
         return match self
             .address_repository
             .deposit(
-                AddressEntity::new(coin_with_address.public_key, Arc::clone(&self.timer_helper)),
+                AddressEntity::new(
+                    coin_with_address.address,
+                    None,
+                    Arc::clone(&self.timer_helper),
+                ),
                 coin_with_address.amount,
             )
             .await
@@ -79,7 +138,11 @@ impl AddressUsecase {
         return match self
             .address_repository
             .withdraw(
-                AddressEntity::new(coin_with_address.public_key, Arc::clone(&self.timer_helper)),
+                AddressEntity::new(
+                    coin_with_address.address,
+                    None,
+                    Arc::clone(&self.timer_helper),
+                ),
                 coin_with_address.amount,
             )
             .await
@@ -88,4 +151,93 @@ impl AddressUsecase {
             Err(e) => Err(Box::new(APIAddressError::UpdateBalanceError(e))),
         };
     }
+
+    pub async fn find_address(
+        &self,
+        address: String,
+    ) -> Result<Option<AddressEntity>, Box<dyn IntoErrorResponse>> {
+        return match self.address_repository.get_by_address(address).await {
+            Ok(address) => Ok(address),
+            Err(e) => Err(Box::new(APIAddressError::FindAddressError(e))),
+        };
+    }
+
+    pub async fn get_address(
+        &self,
+        address: String,
+    ) -> Result<AddressEntity, Box<dyn IntoErrorResponse>> {
+        return match self
+            .address_repository
+            .get_by_address(address.clone())
+            .await
+        {
+            Ok(Some(found)) => Ok(found),
+            Ok(None) => Err(Box::new(APIAddressError::AddressNotFound(address))),
+            Err(e) => Err(Box::new(APIAddressError::FindAddressError(e))),
+        };
+    }
+
+    pub async fn deposit_coin_with_session(
+        &self,
+        session: &mut BoxedSession,
+        coin_with_address: CoinWithAddress,
+    ) -> Result<(), Box<dyn IntoErrorResponse>> {
+        return match self
+            .address_repository
+            .deposit_with_session(
+                session,
+                AddressEntity::new(
+                    coin_with_address.address,
+                    None,
+                    Arc::clone(&self.timer_helper),
+                ),
+                coin_with_address.amount,
+            )
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(APIAddressError::UpdateBalanceError(e))),
+        };
+    }
+
+    pub async fn withdraw_coin_with_session(
+        &self,
+        session: &mut BoxedSession,
+        coin_with_address: CoinWithAddress,
+    ) -> Result<(), Box<dyn IntoErrorResponse>> {
+        return match self
+            .address_repository
+            .withdraw_with_session(
+                session,
+                AddressEntity::new(
+                    coin_with_address.address,
+                    None,
+                    Arc::clone(&self.timer_helper),
+                ),
+                coin_with_address.amount,
+            )
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(APIAddressError::UpdateBalanceError(e))),
+        };
+    }
+
+    pub async fn increment_nonce_with_session(
+        &self,
+        session: &mut BoxedSession,
+        address: String,
+    ) -> Result<(), Box<dyn IntoErrorResponse>> {
+        return match self
+            .address_repository
+            .increment_nonce_with_session(
+                session,
+                AddressEntity::new(address, None, Arc::clone(&self.timer_helper)),
+            )
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(APIAddressError::UpdateBalanceError(e))),
+        };
+    }
 }
@@ -1,15 +1,32 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    entities::block_entity::BlockEntity,
-    errors::{block_error::APIBlockError, error::IntoErrorResponse},
-    models::address_model::CoinWithAddress,
-    repository::block_repository::SharedBlockRepository,
+    chain_helper, crypto_helper,
+    entities::{
+        block_entity::BlockEntity,
+        transaction_entity::{COINBASE_ADDRESS, TransactionEntity, TransactionStatus},
+    },
+    errors::{
+        block_error::APIBlockError, error::IntoErrorResponse,
+        transaction_error::APITransactionError,
+    },
+    models::{
+        address_model::CoinWithAddress,
+        block_model::{ChainReport, SignatureFailure, SignatureReport, TransactionProof},
+        transaction_model::{SignatureScheme, TransactionPayload},
+    },
+    pow_helper,
+    repository::{
+        block_repository::SharedBlockRepository,
+        session::{BoxedSession, SharedSessionFactory},
+    },
+    setting::Setting,
+    signature_helper,
     timer_helper::IntoTimerHelperShared,
-    usecases::transaction_usecase::TransactionUsecase,
+    usecases::{mempool_usecase::MempoolUsecase, transaction_usecase::TransactionUsecase},
 };
 use bson::oid::ObjectId;
-use sha2::{Digest, Sha256};
+use tracing::{error, warn};
 
 use super::address_usecase::AddressUsecase;
 
@@ -17,6 +34,9 @@ pub struct BlockUsecase {
     block_repo: SharedBlockRepository,
     tx_usecase: Arc<TransactionUsecase>,
     addr_usecase: Arc<AddressUsecase>,
+    mempool: Arc<MempoolUsecase>,
+    session_factory: SharedSessionFactory,
+    setting: Arc<Setting>,
     timer_helper: IntoTimerHelperShared,
 }
 
@@ -25,12 +45,18 @@ impl BlockUsecase {
         block_repo: SharedBlockRepository,
         tx_usecase: Arc<TransactionUsecase>,
         addr_usecase: Arc<AddressUsecase>,
+        mempool: Arc<MempoolUsecase>,
+        session_factory: SharedSessionFactory,
+        setting: Arc<Setting>,
         timer_helper: IntoTimerHelperShared,
     ) -> Arc<Self> {
         Arc::new(Self {
             block_repo,
             tx_usecase,
             addr_usecase,
+            mempool,
+            session_factory,
+            setting,
             timer_helper,
         })
     }
@@ -43,89 +69,315 @@ impl BlockUsecase {
         let previous_hash = latest_block.clone().map(|b| b.hash).unwrap_or_default();
         let index = latest_block.map(|b| b.index + 1).unwrap_or(1);
 
-        let txs = self.tx_usecase.get_all_pending().await?;
+        let txs = self.mempool.select_for_block().await?;
+        let (txs, invalid) = self.check_signatures(txs).await?;
+        let (accepted, rejected) = self.select_transactions(txs).await?;
+        let mut coinbase = self.coinbase(index, &accepted).await?;
 
-        let tx_ids: Vec<ObjectId> = txs.iter().filter_map(|tx| tx.id).collect();
+        let tx_ids: Vec<ObjectId> = std::iter::once(&coinbase)
+            .chain(accepted.iter())
+            .filter_map(|tx| tx.id)
+            .collect();
 
-        let raw = format!("{:?}{:?}", tx_ids, previous_hash);
-        let hash = format!("{:x}", Sha256::digest(raw.as_bytes()));
-
-        let block = BlockEntity::new(
+        let recent_blocks = self
+            .block_repo
+            .find_recent(self.setting.mining.retarget_interval)
+            .await
+            .map_err(|e| {
+                Box::new(APIBlockError::FindBlockError(e)) as Box<dyn IntoErrorResponse>
+            })?;
+        let difficulty = pow_helper::next_difficulty(&recent_blocks, &self.setting.mining);
+        let leaves: Vec<[u8; 32]> = std::iter::once(&coinbase)
+            .chain(accepted.iter())
+            .map(crypto_helper::transaction_hash)
+            .collect();
+        let mut block = BlockEntity::new(
             index,
-            tx_ids.clone(),
+            tx_ids,
+            leaves.iter().map(hex::encode).collect(),
             previous_hash,
-            hash.clone(),
-            0,
+            hex::encode(crypto_helper::merkle_root(&leaves)),
+            difficulty,
             Arc::clone(&self.timer_helper),
         );
 
-        let inserted_id = match self.block_repo.insert(block).await {
-            Ok(id) => id,
-            Err(e) => return Err(Box::new(APIBlockError::InsertBlockError(e))),
+        let block = tokio::task::spawn_blocking(move || {
+            pow_helper::mine(&mut block)?;
+            Ok::<BlockEntity, String>(block)
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|mined| mined)
+        .map_err(|e| Box::new(APIBlockError::MiningError(e)) as Box<dyn IntoErrorResponse>)?;
+
+        coinbase.block_hash = Some(block.hash.clone());
+
+        let mut session =
+            self.session_factory.start().await.map_err(|e| {
+                Box::new(APIBlockError::SessionError(e)) as Box<dyn IntoErrorResponse>
+            })?;
+
+        let applied = self
+            .apply_block(&mut session, block, coinbase, accepted, rejected, invalid)
+            .await
+            .map_err(|e| e.error());
+
+        return match applied {
+            Ok(inserted_id) => match session.commit().await {
+                Ok(()) => Ok(inserted_id),
+                Err(e) => Err(Box::new(APIBlockError::SessionError(e))),
+            },
+            Err(response) => {
+                if let Err(e) = session.abort().await {
+                    error!("build_block: abort failed: {}", e);
+                }
+                Err(Box::new(response))
+            }
         };
+    }
+
+    /// Re-verifies the signatures of the candidates as a batch and splits
+    /// off those that fail, keeping the others in order.
+    async fn check_signatures(
+        &self,
+        txs: Vec<TransactionEntity>,
+    ) -> Result<(Vec<TransactionEntity>, Vec<ObjectId>), Box<dyn IntoErrorResponse>> {
+        let (txs, failures) = self.verify_signatures(txs).await?;
+        if failures.is_empty() {
+            return Ok((txs, vec![]));
+        }
+
+        for failure in &failures {
+            warn!(
+                "build_block: dropping transaction {} with a bad signature: {}",
+                failure.tx_hash, failure.error
+            );
+        }
+        let (invalid, valid): (Vec<TransactionEntity>, Vec<TransactionEntity>) = txs
+            .into_iter()
+            .partition(|tx| failures.iter().any(|f| f.tx_hash == tx.hash));
+        return Ok((valid, invalid.into_iter().filter_map(|tx| tx.id).collect()));
+    }
+
+    /// Runs `signature_helper::verify_transactions` off the async runtime and
+    /// hands `txs` back with the failures.
+    async fn verify_signatures(
+        &self,
+        txs: Vec<TransactionEntity>,
+    ) -> Result<(Vec<TransactionEntity>, Vec<SignatureFailure>), Box<dyn IntoErrorResponse>> {
+        let chain_id = self.setting.chain.id;
+        return tokio::task::spawn_blocking(move || {
+            let failures = signature_helper::verify_transactions(&txs, chain_id);
+            (txs, failures)
+        })
+        .await
+        .map_err(|e| {
+            Box::new(APIBlockError::VerifySignaturesError(e.to_string()))
+                as Box<dyn IntoErrorResponse>
+        });
+    }
+
+    /// Splits candidates, already in mempool priority order, into those that
+    /// can be applied against current balances and nonces and those that
+    /// must be rejected. Candidates after a nonce gap are left pending.
+    async fn select_transactions(
+        &self,
+        txs: Vec<TransactionEntity>,
+    ) -> Result<(Vec<TransactionEntity>, Vec<ObjectId>), Box<dyn IntoErrorResponse>> {
+        // balance and next expected nonce, None when the address does not exist
+        let mut accounts: HashMap<String, Option<(u64, u64)>> = HashMap::new();
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
 
-        for tx_id in tx_ids {
-            let tx = self.tx_usecase.get_by_id(tx_id).await?;
-
-            if self
-                .addr_usecase
-                .withdraw_coin(CoinWithAddress {
-                    public_key: tx.from.clone(),
-                    amount: tx.amount,
-                })
-                .await
-                .is_err()
-            {
-                self.tx_usecase.reject_transaction(tx_id).await.ok();
+        for tx in txs {
+            let Some(tx_id) = tx.id else {
                 continue;
+            };
+
+            for key in [&tx.from, &tx.to] {
+                if !accounts.contains_key(key) {
+                    let account = self
+                        .addr_usecase
+                        .find_address(key.clone())
+                        .await?
+                        .map(|a| (a.balance, a.nonce));
+                    accounts.insert(key.clone(), account);
+                }
             }
 
-            if self
-                .addr_usecase
-                .deposit_coin(CoinWithAddress {
-                    public_key: tx.to.clone(),
-                    amount: tx.amount,
-                })
-                .await
-                .is_err()
-            {
-                self.addr_usecase
-                    .deposit_coin(CoinWithAddress {
-                        public_key: tx.from,
-                        amount: tx.amount,
-                    })
-                    .await
-                    .ok(); // rollback withdraw
-                self.tx_usecase.reject_transaction(tx_id).await.ok();
-                continue;
+            let sender = accounts[&tx.from];
+            let receiver_exists = accounts[&tx.to].is_some();
+
+            match sender {
+                // an earlier nonce expired or was evicted, the transaction
+                // stays pending until the sender sends that nonce again
+                Some((_, nonce)) if tx.nonce > nonce => {}
+                Some((balance, nonce))
+                    if receiver_exists
+                        && tx
+                            .amount
+                            .checked_add(tx.fee)
+                            .is_some_and(|cost| balance >= cost)
+                        && nonce == tx.nonce =>
+                {
+                    let cost = tx.amount + tx.fee;
+                    let credited = match accounts[&tx.to] {
+                        Some((receiver, _)) => receiver.checked_add(tx.amount),
+                        None => None,
+                    };
+                    let Some(credited) = credited else {
+                        rejected.push(tx_id);
+                        continue;
+                    };
+
+                    accounts.insert(tx.from.clone(), Some((balance - cost, nonce + 1)));
+                    if let Some(Some((receiver, _))) = accounts.get_mut(&tx.to) {
+                        *receiver = credited;
+                    }
+                    accepted.push(tx);
+                }
+                _ => rejected.push(tx_id),
             }
+        }
 
-            if self
-                .tx_usecase
-                .confirm_transaction(tx_id, hash.clone())
-                .await
-                .is_err()
-            {
-                self.tx_usecase.reject_transaction(tx_id).await.ok();
-                self.addr_usecase
-                    .withdraw_coin(CoinWithAddress {
-                        public_key: tx.to,
-                        amount: tx.amount,
-                    })
-                    .await
-                    .ok(); // rollback deposit
-                self.addr_usecase
-                    .deposit_coin(CoinWithAddress {
-                        public_key: tx.from,
-                        amount: tx.amount,
-                    })
-                    .await
-                    .ok(); // rollback withdraw
-                continue;
+        return Ok((accepted, rejected));
+    }
+
+    /// Builds the reward transaction paying the block subsidy and the fees of
+    /// `accepted` to the configured miner address.
+    async fn coinbase(
+        &self,
+        index: u64,
+        accepted: &[TransactionEntity],
+    ) -> Result<TransactionEntity, Box<dyn IntoErrorResponse>> {
+        let miner_address = match &self.setting.mining.miner_address {
+            Some(miner_address) => miner_address.clone(),
+            None => return Err(Box::new(APIBlockError::MinerAddressNotSet)),
+        };
+        if self
+            .addr_usecase
+            .find_address(miner_address.clone())
+            .await?
+            .is_none()
+        {
+            return Err(Box::new(APIBlockError::MinerAddressNotFound(miner_address)));
+        }
+
+        let fee = match accepted
+            .iter()
+            .try_fold(0u64, |sum, tx| sum.checked_add(tx.fee))
+        {
+            Some(fee) => fee,
+            None => {
+                return Err(Box::new(APIBlockError::AmountOverflow(
+                    "block fees".to_string(),
+                )));
             }
+        };
+
+        let payload = TransactionPayload {
+            chain_id: self.setting.chain.id,
+            from: COINBASE_ADDRESS.to_string(),
+            to: miner_address,
+            amount: self.setting.mining.block_reward,
+            fee,
+            nonce: index,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+        };
+        let mut coinbase = TransactionEntity::new(
+            &payload,
+            String::new(),
+            String::new(),
+            TransactionStatus::Confirmed,
+            Arc::clone(&self.timer_helper),
+        );
+        coinbase.id = Some(ObjectId::new());
+
+        return Ok(coinbase);
+    }
+
+    async fn apply_block(
+        &self,
+        session: &mut BoxedSession,
+        block: BlockEntity,
+        coinbase: TransactionEntity,
+        accepted: Vec<TransactionEntity>,
+        rejected: Vec<ObjectId>,
+        invalid: Vec<ObjectId>,
+    ) -> Result<ObjectId, Box<dyn IntoErrorResponse>> {
+        let hash = block.hash.clone();
+        let reward = match coinbase.amount.checked_add(coinbase.fee) {
+            Some(amount) => CoinWithAddress {
+                address: coinbase.to.clone(),
+                amount,
+            },
+            None => {
+                return Err(Box::new(APIBlockError::AmountOverflow(
+                    "block reward".to_string(),
+                )));
+            }
+        };
+
+        let inserted_id = match self.block_repo.insert_with_session(session, block).await {
+            Ok(id) => id,
+            Err(e) => return Err(Box::new(APIBlockError::InsertBlockError(e))),
+        };
+
+        self.tx_usecase
+            .insert_transaction_with_session(session, coinbase)
+            .await?;
+        self.addr_usecase
+            .deposit_coin_with_session(session, reward)
+            .await?;
+
+        for tx in accepted {
+            let Some(tx_id) = tx.id else {
+                continue;
+            };
+
+            self.addr_usecase
+                .withdraw_coin_with_session(
+                    session,
+                    CoinWithAddress {
+                        address: tx.from.clone(),
+                        amount: tx.amount + tx.fee,
+                    },
+                )
+                .await?;
+
+            self.addr_usecase
+                .increment_nonce_with_session(session, tx.from)
+                .await?;
+
+            self.addr_usecase
+                .deposit_coin_with_session(
+                    session,
+                    CoinWithAddress {
+                        address: tx.to,
+                        amount: tx.amount,
+                    },
+                )
+                .await?;
+
+            self.tx_usecase
+                .confirm_transaction_with_session(session, tx_id, hash.clone())
+                .await?;
         }
 
-        Ok(inserted_id)
+        for tx_id in rejected {
+            self.tx_usecase
+                .reject_transaction_with_session(session, tx_id)
+                .await?;
+        }
+
+        for tx_id in invalid {
+            self.tx_usecase
+                .invalidate_transaction_with_session(session, tx_id)
+                .await?;
+        }
+
+        return Ok(inserted_id);
     }
 
     pub async fn get_block_by_hash(
@@ -151,10 +403,114 @@ impl BlockUsecase {
         };
     }
 
-    pub async fn verify_chain(&self) -> Result<(), Box<dyn IntoErrorResponse>> {
+    pub async fn verify_chain(&self) -> Result<ChainReport, Box<dyn IntoErrorResponse>> {
         return match self.block_repo.is_chain_valid().await {
-            Ok(()) => Ok(()),
-            Err(e) => return Err(Box::new(APIBlockError::InvalidChain(e))),
+            Ok(report) => Ok(report),
+            Err(e) => return Err(Box::new(APIBlockError::FindBlockError(e))),
+        };
+    }
+
+    /// Re-verifies every signature of a block in parallel and reports the
+    /// transactions that fail, including any the block lists but that can
+    /// no longer be found.
+    pub async fn verify_block_signatures(
+        &self,
+        hash: String,
+    ) -> Result<SignatureReport, Box<dyn IntoErrorResponse>> {
+        let block = self.get_block_by_hash(hash).await?;
+        let mut found: HashMap<ObjectId, TransactionEntity> = self
+            .tx_usecase
+            .get_by_block_hash(block.hash.clone())
+            .await?
+            .into_iter()
+            .filter_map(|tx| Some((tx.id?, tx)))
+            .collect();
+
+        let mut missing = Vec::new();
+        let mut txs = Vec::new();
+        for (position, tx_id) in block.transactions.iter().enumerate() {
+            match found.remove(tx_id) {
+                Some(tx) => txs.push(tx),
+                None => missing.push(SignatureFailure {
+                    tx_id: Some(tx_id.to_hex()),
+                    tx_hash: block
+                        .transaction_hashes
+                        .get(position)
+                        .cloned()
+                        .unwrap_or_default(),
+                    error: "transaction not found".to_string(),
+                }),
+            }
+        }
+
+        let (txs, mut failed) = self.verify_signatures(txs).await?;
+        failed.extend(missing);
+        // the coinbase is unsigned, verify_signatures skips it
+        let checked = txs.iter().filter(|tx| tx.from != COINBASE_ADDRESS).count();
+        return Ok(SignatureReport {
+            block_hash: block.hash,
+            checked: checked as u64,
+            failed,
+        });
+    }
+
+    pub async fn get_transaction_proof(
+        &self,
+        tx_id: ObjectId,
+    ) -> Result<TransactionProof, Box<dyn IntoErrorResponse>> {
+        let tx = self.tx_usecase.get_by_id(tx_id).await?;
+        let block_hash = match tx.block_hash {
+            Some(block_hash) => block_hash,
+            None => return Err(Box::new(APITransactionError::NotConfirmed(tx_id))),
+        };
+        let block = self.get_block_by_hash(block_hash).await?;
+
+        let not_in_block = || {
+            Box::new(APIBlockError::TransactionNotInBlock(
+                tx_id.to_hex(),
+                block.hash.clone(),
+            )) as Box<dyn IntoErrorResponse>
+        };
+        let position = match block.transactions.iter().position(|id| *id == tx_id) {
+            Some(position) => position,
+            None => return Err(not_in_block()),
+        };
+        let leaves = match chain_helper::transaction_leaves(&block.transaction_hashes) {
+            Some(leaves) => leaves,
+            None => return Err(not_in_block()),
+        };
+        let branch = match crypto_helper::merkle_branch(&leaves, position) {
+            Some(branch) => branch,
+            None => return Err(not_in_block()),
+        };
+
+        return Ok(TransactionProof {
+            tx_id: tx_id.to_hex(),
+            tx_hash: hex::encode(leaves[position]),
+            block_hash: block.hash.clone(),
+            block_index: block.index,
+            merkle_root: block.merkle_root.clone(),
+            position: position as u64,
+            branch: branch.iter().map(hex::encode).collect(),
+        });
+    }
+
+    /// Coins issued by block rewards, every other transfer only moves coins.
+    pub async fn get_total_supply(&self) -> Result<u64, Box<dyn IntoErrorResponse>> {
+        let coinbases = self
+            .tx_usecase
+            .get_by_sender(COINBASE_ADDRESS.to_string())
+            .await?;
+
+        return match coinbases
+            .iter()
+            .filter(|tx| tx.status == TransactionStatus::Confirmed)
+            .try_fold(0u64, |sum, tx| sum.checked_add(tx.amount))
+        {
+            Some(supply) => Ok(supply),
+            None => Err(Box::new(APIBlockError::AmountOverflow(
+                "total supply".to_string(),
+            ))),
         };
     }
 }
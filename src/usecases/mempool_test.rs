@@ -0,0 +1,337 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bson::oid::ObjectId;
+
+    use crate::{
+        crypto_helper,
+        entities::transaction_entity::{TransactionEntity, TransactionStatus},
+        errors::error::IntoErrorResponse,
+        models::transaction_model::SignatureScheme,
+        repository::{
+            memory_store::{MemorySessionFactory, MemoryStore},
+            session::BoxedSession,
+            transaction_repository::{MemoryTransactionRepository, MockTransactionRepository},
+        },
+        setting::Setting,
+        timer_helper::TimerHelper,
+        usecases::mempool_usecase::{
+            MempoolUsecase, choose_eviction, is_expired, order_by_priority,
+        },
+    };
+
+    fn transaction(from: &str, fee: u64, nonce: u64, timestamp: i64) -> TransactionEntity {
+        return TransactionEntity {
+            id: None,
+            hash: String::new(),
+            block_hash: None,
+            from: String::from(from),
+            to: String::from("receiver"),
+            amount: 1,
+            fee,
+            nonce,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+            public_key: String::new(),
+            signature: String::new(),
+            timestamp,
+            status: TransactionStatus::Pending,
+        };
+    }
+
+    fn summary(txs: &[TransactionEntity]) -> Vec<(String, u64)> {
+        return txs.iter().map(|tx| (tx.from.clone(), tx.nonce)).collect();
+    }
+
+    #[test]
+    fn order_by_fee_rate_test() {
+        let txs = vec![
+            transaction("a", 1, 0, 0),
+            transaction("b", 10, 0, 0),
+            transaction("c", 5, 0, 0),
+        ];
+
+        let ordered = order_by_priority(txs, 10, u64::MAX);
+        assert_eq!(
+            summary(&ordered),
+            vec![
+                (String::from("b"), 0),
+                (String::from("c"), 0),
+                (String::from("a"), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn order_equal_fee_rate_by_age_test() {
+        let txs = vec![transaction("a", 5, 0, 20), transaction("b", 5, 0, 10)];
+
+        let ordered = order_by_priority(txs, 10, u64::MAX);
+        assert_eq!(ordered[0].from, "b");
+    }
+
+    #[test]
+    fn order_keeps_sender_nonce_order_test() {
+        // the high fee nonce 1 must wait for the sender's low fee nonce 0
+        let txs = vec![
+            transaction("a", 100, 1, 0),
+            transaction("a", 1, 0, 0),
+            transaction("b", 10, 0, 0),
+        ];
+
+        let ordered = order_by_priority(txs, 10, u64::MAX);
+        assert_eq!(
+            summary(&ordered),
+            vec![
+                (String::from("b"), 0),
+                (String::from("a"), 0),
+                (String::from("a"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn order_respects_transaction_limit_test() {
+        let txs = vec![
+            transaction("a", 1, 0, 0),
+            transaction("b", 2, 0, 0),
+            transaction("c", 3, 0, 0),
+        ];
+
+        let ordered = order_by_priority(txs, 2, u64::MAX);
+        assert_eq!(ordered.len(), 2);
+        assert_eq!(ordered[0].from, "c");
+        assert_eq!(ordered[1].from, "b");
+    }
+
+    #[test]
+    fn order_respects_byte_limit_test() {
+        let txs = vec![transaction("a", 2, 0, 0), transaction("b", 1, 0, 0)];
+        let size = crypto_helper::transaction_bytes(&txs[0]).len() as u64;
+
+        let ordered = order_by_priority(txs, 10, size);
+        assert_eq!(summary(&ordered), vec![(String::from("a"), 0)]);
+    }
+
+    #[test]
+    fn expired_by_ttl_test() {
+        let tx = transaction("a", 1, 0, 100);
+
+        assert!(!is_expired(&tx, 160, 60));
+        assert!(is_expired(&tx, 161, 60));
+    }
+
+    #[test]
+    fn expired_by_valid_until_test() {
+        let mut tx = transaction("a", 1, 0, 100);
+        tx.valid_until = Some(120);
+
+        assert!(!is_expired(&tx, 120, 3600));
+        assert!(is_expired(&tx, 121, 3600));
+    }
+
+    fn pending(from: &str, fee: u64, nonce: u64) -> TransactionEntity {
+        let mut tx = transaction(from, fee, nonce, 0);
+        tx.id = Some(ObjectId::new());
+        return tx;
+    }
+
+    /// A mempool of `max_transactions` over `pending`, expecting `evicted`
+    /// to be rejected when set.
+    fn mempool(
+        max_transactions: u64,
+        pending: Vec<TransactionEntity>,
+        evicted: Option<ObjectId>,
+    ) -> Arc<MempoolUsecase> {
+        let mut transaction_repository_mock = MockTransactionRepository::new();
+        transaction_repository_mock
+            .expect_find_all_pending()
+            .returning(move || {
+                let pending = pending.clone();
+                Box::pin(async move { Ok(pending) })
+            });
+        match evicted {
+            Some(id) => {
+                transaction_repository_mock
+                    .expect_update_status_with_session()
+                    .withf(move |_, tx_id, status| {
+                        *tx_id == id && *status == TransactionStatus::Rejected
+                    })
+                    .times(1)
+                    .returning(|_, _, _| Box::pin(async { Ok(()) }));
+            }
+            None => {
+                transaction_repository_mock
+                    .expect_update_status_with_session()
+                    .never();
+            }
+        }
+        transaction_repository_mock.expect_update_status().never();
+
+        return MempoolUsecase::creation(
+            Arc::new(transaction_repository_mock),
+            setting(max_transactions),
+            TimerHelper::Mock.creation(),
+        );
+    }
+
+    fn setting(max_transactions: u64) -> Arc<Setting> {
+        let toml = include_str!("../../Settings.toml").replace(
+            "max_transactions = 5000",
+            &format!("max_transactions = {}", max_transactions),
+        );
+        return Setting::from_toml(&toml).unwrap();
+    }
+
+    async fn session() -> BoxedSession {
+        return MemorySessionFactory::creation(MemoryStore::creation())
+            .start()
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn eviction_takes_highest_nonce_of_a_chain_test() {
+        // a's nonce 0 pays least, but evicting it would strand nonce 1
+        let chain = [pending("a", 1, 0), pending("a", 3, 1)];
+        let other = pending("b", 2, 0);
+        let incoming = transaction("c", 10, 0, 0);
+
+        let evicted = choose_eviction(
+            vec![chain[0].clone(), other.clone(), chain[1].clone()],
+            &incoming,
+        );
+        assert_eq!(evicted.ok(), Some(other));
+
+        let evicted = choose_eviction(vec![chain[0].clone(), chain[1].clone()], &incoming);
+        assert_eq!(evicted.ok(), Some(chain[1].clone()));
+    }
+
+    #[test]
+    fn eviction_skips_incoming_sender_test() {
+        let own = pending("a", 1, 0);
+        let other = pending("b", 5, 0);
+        let incoming = transaction("a", 10, 1, 0);
+
+        let evicted = choose_eviction(vec![own, other.clone()], &incoming);
+        assert_eq!(evicted.ok(), Some(other));
+    }
+
+    #[test]
+    fn eviction_refuses_when_only_incoming_sender_test() {
+        let incoming = transaction("a", 10, 2, 0);
+
+        let error = match choose_eviction(vec![pending("a", 1, 0), pending("a", 1, 1)], &incoming) {
+            Ok(_) => panic!("evicted a transaction of the incoming sender"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, "mempool is full and only holds transactions from a");
+    }
+
+    #[test]
+    fn eviction_refuses_lower_fee_rate_test() {
+        let incoming = transaction("c", 1, 0, 0);
+
+        assert!(choose_eviction(vec![pending("a", 5, 0)], &incoming).is_err());
+    }
+
+    #[tokio::test]
+    async fn admit_below_capacity_test() {
+        let txs = vec![pending("a", 1, 0)];
+
+        let result = mempool(2, txs, None)
+            .admit(&mut session().await, &transaction("b", 0, 0, 0))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn admit_evicts_when_full_test() {
+        let txs = vec![pending("a", 1, 0), pending("a", 2, 1), pending("b", 3, 0)];
+        let evicted = txs[1].id;
+
+        let result = mempool(3, txs, evicted)
+            .admit(&mut session().await, &transaction("c", 10, 0, 0))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn admit_refuses_when_full_test() {
+        let txs = vec![pending("a", 5, 0), pending("b", 5, 0)];
+
+        let result = mempool(2, txs, None)
+            .admit(&mut session().await, &transaction("c", 1, 0, 0))
+            .await;
+        let error = match result {
+            Ok(()) => panic!("admitted into a full mempool"),
+            Err(e) => e.error(),
+        };
+        assert!(
+            error
+                .error
+                .starts_with("mempool is full, fee rate must be above")
+        );
+    }
+
+    #[tokio::test]
+    async fn admit_evicts_inside_the_session_test() {
+        let store = MemoryStore::creation();
+        let sessions = MemorySessionFactory::creation(Arc::clone(&store));
+        let txs = MemoryTransactionRepository::creation(store);
+        let victim = pending("a", 1, 0);
+        txs.insert(victim.clone()).await.unwrap();
+        let mempool =
+            MempoolUsecase::creation(Arc::clone(&txs), setting(1), TimerHelper::Mock.creation());
+        let incoming = transaction("c", 10, 0, 0);
+
+        // the insert failed, the victim stays pending
+        let mut session = sessions.start().await.unwrap();
+        assert!(mempool.admit(&mut session, &incoming).await.is_ok());
+        session.abort().await.unwrap();
+        let status = txs
+            .find_by_id(victim.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap()
+            .status;
+        assert_eq!(status, TransactionStatus::Pending);
+
+        let mut session = sessions.start().await.unwrap();
+        assert!(mempool.admit(&mut session, &incoming).await.is_ok());
+        txs.insert_with_session(&mut session, incoming)
+            .await
+            .unwrap();
+        session.commit().await.unwrap();
+        let status = txs
+            .find_by_id(victim.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap()
+            .status;
+        assert_eq!(status, TransactionStatus::Rejected);
+    }
+
+    #[tokio::test]
+    async fn stats_test() {
+        let txs = vec![pending("a", 1, 0), pending("b", 4, 0)];
+        let size = crypto_helper::transaction_bytes(&txs[0]).len() as u64;
+
+        let stats = mempool(10, txs, None).stats().await.ok().unwrap();
+        assert_eq!(stats.transactions, 2);
+        assert_eq!(stats.bytes, 2 * size);
+        assert_eq!(stats.total_fees, 5);
+        assert_eq!(stats.min_fee_rate, 1.0 / size as f64);
+        assert_eq!(stats.max_fee_rate, 4.0 / size as f64);
+        assert_eq!(stats.max_transactions, 10);
+    }
+
+    #[tokio::test]
+    async fn stats_total_fees_saturate_test() {
+        let txs = vec![pending("a", u64::MAX, 0), pending("b", 1, 0)];
+
+        let stats = mempool(10, txs, None).stats().await.ok().unwrap();
+        assert_eq!(stats.total_fees, u64::MAX);
+    }
+}
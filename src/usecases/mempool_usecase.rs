@@ -0,0 +1,285 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::{
+    crypto_helper,
+    entities::transaction_entity::{TransactionEntity, TransactionStatus},
+    errors::{error::IntoErrorResponse, transaction_error::APITransactionError},
+    models::mempool_model::MempoolStats,
+    repository::{session::BoxedSession, transaction_repository::SharedTransactionRepository},
+    setting::Setting,
+    timer_helper::IntoTimerHelperShared,
+};
+
+pub struct MempoolUsecase {
+    tx_repo: SharedTransactionRepository,
+    setting: Arc<Setting>,
+    timer_helper: IntoTimerHelperShared,
+}
+
+/// A pending transaction with its canonical size, ordered by fee rate.
+/// Equal fee rates fall back to the older transaction first.
+struct Candidate {
+    tx: TransactionEntity,
+    size: u64,
+}
+
+impl Candidate {
+    fn new(tx: TransactionEntity) -> Self {
+        let size = crypto_helper::transaction_bytes(&tx).len() as u64;
+        return Self { tx, size };
+    }
+
+    fn fee_rate(&self) -> f64 {
+        return self.tx.fee as f64 / self.size as f64;
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let rate = (self.tx.fee as u128 * other.size as u128)
+            .cmp(&(other.tx.fee as u128 * self.size as u128));
+        return rate.then_with(|| other.tx.timestamp.cmp(&self.tx.timestamp));
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Candidate {}
+
+/// Whether `tx` has outlived the pending `ttl` or its own `valid_until`.
+pub fn is_expired(tx: &TransactionEntity, now: i64, ttl: i64) -> bool {
+    if tx.timestamp.saturating_add(ttl) < now {
+        return true;
+    }
+    return match tx.valid_until {
+        Some(valid_until) => valid_until < now,
+        None => false,
+    };
+}
+
+/// Orders transactions by fee rate while keeping each sender's
+/// transactions in nonce order, stopping at the count and byte limits.
+pub fn order_by_priority(
+    txs: Vec<TransactionEntity>,
+    max_transactions: u64,
+    max_bytes: u64,
+) -> Vec<TransactionEntity> {
+    let mut by_sender: HashMap<String, VecDeque<Candidate>> = HashMap::new();
+    for tx in txs {
+        by_sender
+            .entry(tx.from.clone())
+            .or_default()
+            .push_back(Candidate::new(tx));
+    }
+
+    let mut heap = BinaryHeap::new();
+    for queue in by_sender.values_mut() {
+        queue
+            .make_contiguous()
+            .sort_by_key(|c| (c.tx.nonce, c.tx.timestamp));
+        if let Some(head) = queue.pop_front() {
+            heap.push(head);
+        }
+    }
+
+    let mut selected = Vec::new();
+    let mut bytes = 0;
+    while let Some(candidate) = heap.pop() {
+        if selected.len() as u64 >= max_transactions {
+            break;
+        }
+        // a sender's later nonces can't be included without this one
+        if bytes + candidate.size > max_bytes {
+            continue;
+        }
+
+        bytes += candidate.size;
+        if let Some(next) = by_sender
+            .get_mut(&candidate.tx.from)
+            .and_then(|queue| queue.pop_front())
+        {
+            heap.push(next);
+        }
+        selected.push(candidate.tx);
+    }
+
+    return selected;
+}
+
+/// The pending transaction to evict so `incoming` fits in a full pool.
+/// Only the highest nonce of a sender's chain may go, so every transaction
+/// left can still be mined, and never one of the sender of `incoming`,
+/// whose chain `incoming` extends. The cheapest such tail goes when it pays
+/// a lower fee rate than `incoming`, otherwise `incoming` is refused.
+pub fn choose_eviction(
+    pending: Vec<TransactionEntity>,
+    incoming: &TransactionEntity,
+) -> Result<TransactionEntity, APITransactionError> {
+    let mut tails: HashMap<String, TransactionEntity> = HashMap::new();
+    for tx in pending {
+        if tx.from == incoming.from {
+            continue;
+        }
+        match tails.get(&tx.from) {
+            Some(tail) if (tail.nonce, tail.timestamp) >= (tx.nonce, tx.timestamp) => {}
+            _ => {
+                tails.insert(tx.from.clone(), tx);
+            }
+        }
+    }
+
+    let lowest = match tails.into_values().map(Candidate::new).min() {
+        Some(lowest) => lowest,
+        None => {
+            return Err(APITransactionError::MempoolFullOfSender(
+                incoming.from.clone(),
+            ));
+        }
+    };
+    if Candidate::new(incoming.clone()) <= lowest {
+        return Err(APITransactionError::MempoolFull(lowest.fee_rate()));
+    }
+    return Ok(lowest.tx);
+}
+
+impl MempoolUsecase {
+    pub fn creation(
+        tx_repo: SharedTransactionRepository,
+        setting: Arc<Setting>,
+        timer_helper: IntoTimerHelperShared,
+    ) -> Arc<Self> {
+        return Arc::new(Self {
+            tx_repo,
+            setting,
+            timer_helper,
+        });
+    }
+
+    async fn pending(&self) -> Result<Vec<TransactionEntity>, Box<dyn IntoErrorResponse>> {
+        return match self.tx_repo.find_all_pending().await {
+            Ok(txs) => Ok(txs),
+            Err(e) => Err(Box::new(APITransactionError::FindError(e))),
+        };
+    }
+
+    /// Makes room for `tx` when the pool is full by evicting the entry
+    /// `choose_eviction` picks, or refuses `tx` when there is none.
+    /// The eviction is written in `session`, insert `tx` in the same one so
+    /// the victim stays pending when the insert fails.
+    pub async fn admit(
+        &self,
+        session: &mut BoxedSession,
+        tx: &TransactionEntity,
+    ) -> Result<(), Box<dyn IntoErrorResponse>> {
+        let pending = self.pending().await?;
+        if (pending.len() as u64) < self.setting.mempool.max_transactions {
+            return Ok(());
+        }
+
+        let evicted = match choose_eviction(pending, tx) {
+            Ok(evicted) => evicted,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let evicted_id = match evicted.id {
+            Some(id) => id,
+            None => {
+                return Err(Box::new(APITransactionError::MempoolFull(
+                    Candidate::new(evicted).fee_rate(),
+                )));
+            }
+        };
+        return match self
+            .tx_repo
+            .update_status_with_session(session, evicted_id, TransactionStatus::Rejected)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(APITransactionError::UpdateStatusError(e))),
+        };
+    }
+
+    /// Pending transactions for the next block, highest fee rate first and
+    /// within the block limits.
+    pub async fn select_for_block(
+        &self,
+    ) -> Result<Vec<TransactionEntity>, Box<dyn IntoErrorResponse>> {
+        let now = self.timer_helper.now();
+        let ttl = self.setting.mempool.transaction_ttl;
+        let pending = self
+            .pending()
+            .await?
+            .into_iter()
+            .filter(|tx| !is_expired(tx, now, ttl))
+            .collect();
+        return Ok(order_by_priority(
+            pending,
+            self.setting.mempool.max_block_transactions,
+            self.setting.mempool.max_block_bytes,
+        ));
+    }
+
+    pub async fn stats(&self) -> Result<MempoolStats, Box<dyn IntoErrorResponse>> {
+        let candidates: Vec<Candidate> = self
+            .pending()
+            .await?
+            .into_iter()
+            .map(Candidate::new)
+            .collect();
+
+        return Ok(MempoolStats {
+            transactions: candidates.len() as u64,
+            bytes: candidates.iter().map(|c| c.size).sum(),
+            total_fees: candidates
+                .iter()
+                .fold(0u64, |sum, c| sum.saturating_add(c.tx.fee)),
+            min_fee_rate: candidates.iter().min().map(|c| c.fee_rate()).unwrap_or(0.0),
+            max_fee_rate: candidates.iter().max().map(|c| c.fee_rate()).unwrap_or(0.0),
+            max_transactions: self.setting.mempool.max_transactions,
+        });
+    }
+
+    /// Marks pending transactions past their ttl or `valid_until` as expired.
+    pub async fn expire_stale(&self) -> Result<u64, Box<dyn IntoErrorResponse>> {
+        let now = self.timer_helper.now();
+        let submitted_before = now.saturating_sub(self.setting.mempool.transaction_ttl);
+        return match self.tx_repo.expire_pending(submitted_before, now).await {
+            Ok(count) => Ok(count),
+            Err(e) => Err(Box::new(APITransactionError::UpdateStatusError(e))),
+        };
+    }
+
+    /// Runs `expire_stale` every `mempool.sweep_interval` seconds.
+    pub fn spawn_sweeper(self: &Arc<Self>) -> JoinHandle<()> {
+        let mempool = Arc::clone(self);
+        let period = Duration::from_secs(mempool.setting.mempool.sweep_interval.max(1));
+        return tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                match mempool.expire_stale().await.map_err(|e| e.error()) {
+                    Ok(0) => {}
+                    Ok(count) => info!("expired {} pending transactions", count),
+                    Err(e) => error!("expire pending transactions error: {}", e.error),
+                }
+            }
+        });
+    }
+}
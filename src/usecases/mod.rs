@@ -1,4 +1,7 @@
 pub mod address_usecase;
 pub mod address_test;
 pub mod transaction_usecase;
-pub mod block_usecase;
\ No newline at end of file
+pub mod transaction_test;
+pub mod block_usecase;
+pub mod mempool_usecase;
+pub mod mempool_test;
\ No newline at end of file
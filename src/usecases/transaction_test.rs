@@ -0,0 +1,478 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bson::oid::ObjectId;
+    use mockall::{Sequence, predicate::eq};
+
+    use crate::{
+        crypto_helper,
+        entities::{
+            address_entity::AddressEntity,
+            transaction_entity::{TransactionEntity, TransactionStatus},
+        },
+        models::transaction_model::{
+            CreateTransactionRequest, SignatureScheme, TransactionPayload,
+        },
+        repository::{
+            address_repository::MockAddressRepository,
+            memory_store::{MemorySessionFactory, MemoryStore},
+            transaction_repository::MockTransactionRepository,
+        },
+        setting::Setting,
+        timer_helper::TimerHelper,
+        usecases::{
+            mempool_usecase::MempoolUsecase,
+            transaction_usecase::{TransactionUsecase, available_balance, next_nonce},
+        },
+    };
+
+    const SENDER_KEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const SENDER_SECRET_KEY: &str =
+        "0000000000000000000000000000000000000000000000000000000000000001";
+    const SENDER: &str = "RKxTdfmtxtfLDKZBgx6SvNkBtNu9jRYnLh";
+    const RECEIVER: &str = "RLkZJhcqT2ac31oqBeLvo6egULQR54LNxb";
+    const UNKNOWN: &str = "RBVmdffxN89F4UTzexC9y9Xuz37e3L4ecw";
+
+    fn usecase(account_nonce: u64, pending_nonces: Vec<u64>) -> Arc<TransactionUsecase> {
+        return usecase_with(account_nonce, pending_nonces, false, |_, _| {});
+    }
+
+    /// Like `usecase`, `expect` adds the expectations of an accepted
+    /// transaction.
+    fn usecase_with(
+        account_nonce: u64,
+        pending_nonces: Vec<u64>,
+        create_unknown_receiver: bool,
+        expect: impl FnOnce(&mut MockAddressRepository, &mut MockTransactionRepository),
+    ) -> Arc<TransactionUsecase> {
+        let timer_helper = TimerHelper::Mock.creation();
+        let mut address_repository_mock = MockAddressRepository::new();
+        let mut transaction_repository_mock = MockTransactionRepository::new();
+        expect(
+            &mut address_repository_mock,
+            &mut transaction_repository_mock,
+        );
+
+        let mut sender = AddressEntity::new(
+            String::from(SENDER),
+            Some(String::from(SENDER_KEY)),
+            Arc::clone(&timer_helper),
+        );
+        sender.balance = 100;
+        sender.nonce = account_nonce;
+        let receiver = AddressEntity::new(String::from(RECEIVER), None, Arc::clone(&timer_helper));
+        address_repository_mock
+            .expect_get_by_address()
+            .returning(move |address| {
+                let found = match address.as_str() {
+                    SENDER => Some(sender.clone()),
+                    RECEIVER => Some(receiver.clone()),
+                    _ => None,
+                };
+                Box::pin(async move { Ok(found) })
+            });
+
+        let pending: Vec<TransactionEntity> = pending_nonces
+            .into_iter()
+            .map(|nonce| {
+                TransactionEntity::new(
+                    &TransactionPayload {
+                        chain_id: 1,
+                        from: String::from(SENDER),
+                        to: String::from("receiver"),
+                        amount: 1,
+                        fee: 0,
+                        nonce,
+                        valid_until: None,
+                        scheme: SignatureScheme::Ecdsa,
+                    },
+                    String::from(SENDER_KEY),
+                    String::new(),
+                    TransactionStatus::Pending,
+                    Arc::clone(&timer_helper),
+                )
+            })
+            .collect();
+        transaction_repository_mock
+            .expect_find_pending_by_sender()
+            .with(eq(String::from(SENDER)))
+            .returning(move |_| {
+                let pending = pending.clone();
+                Box::pin(async move { Ok(pending) })
+            });
+
+        let toml = include_str!("../../Settings.toml").replace(
+            "create_unknown_receiver = false",
+            &format!("create_unknown_receiver = {}", create_unknown_receiver),
+        );
+        let setting = Setting::from_toml(&toml).unwrap();
+        let transaction_repository = Arc::new(transaction_repository_mock);
+        return TransactionUsecase::creation(
+            transaction_repository.clone(),
+            Arc::new(address_repository_mock),
+            MempoolUsecase::creation(
+                transaction_repository,
+                Arc::clone(&setting),
+                Arc::clone(&timer_helper),
+            ),
+            MemorySessionFactory::creation(MemoryStore::creation()),
+            setting,
+            timer_helper,
+        );
+    }
+
+    fn request(nonce: u64) -> CreateTransactionRequest {
+        return CreateTransactionRequest {
+            from: String::from(SENDER),
+            to: String::from(RECEIVER),
+            amount: 1,
+            fee: 0,
+            nonce,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+            public_key: String::from(SENDER_KEY),
+            signature: String::new(),
+        };
+    }
+
+    /// `request` signed by the sender's key, sent to `to`.
+    fn signed_request(nonce: u64, to: &str) -> CreateTransactionRequest {
+        let mut req = request(nonce);
+        req.to = String::from(to);
+        req.amount = 10;
+        req.fee = 2;
+        let payload = TransactionPayload {
+            chain_id: 1,
+            from: req.from.clone(),
+            to: req.to.clone(),
+            amount: req.amount,
+            fee: req.fee,
+            nonce: req.nonce,
+            valid_until: req.valid_until,
+            scheme: req.scheme,
+        };
+        req.signature = crypto_helper::sign_payload(&payload, SENDER_SECRET_KEY).unwrap();
+        return req;
+    }
+
+    /// Expects the transaction to be admitted into an empty mempool and
+    /// stored, keeping the stored transaction in `stored`.
+    fn expect_stored(
+        transactions: &mut MockTransactionRepository,
+        stored: Arc<Mutex<Option<TransactionEntity>>>,
+        sequence: &mut Sequence,
+    ) -> ObjectId {
+        let tx_id = ObjectId::new();
+        transactions
+            .expect_find_all_pending()
+            .returning(|| Box::pin(async { Ok(vec![]) }));
+        transactions
+            .expect_insert_with_session()
+            .times(1)
+            .in_sequence(sequence)
+            .returning(move |_, tx| {
+                *stored.lock().unwrap() = Some(tx);
+                Box::pin(async move { Ok(tx_id) })
+            });
+        return tx_id;
+    }
+
+    #[tokio::test]
+    async fn create_transaction_test() {
+        let stored = Arc::new(Mutex::new(None));
+        let mut tx_id = None;
+        let usecase = usecase_with(0, vec![0], false, |addresses, transactions| {
+            tx_id = Some(expect_stored(
+                transactions,
+                Arc::clone(&stored),
+                &mut Sequence::new(),
+            ));
+            addresses.expect_insert().never();
+        });
+
+        let result = usecase
+            .create_transaction(signed_request(1, RECEIVER))
+            .await;
+        assert_eq!(result.ok(), tx_id);
+
+        let stored = stored.lock().unwrap().clone().unwrap();
+        assert_eq!(
+            (
+                stored.from.as_str(),
+                stored.to.as_str(),
+                stored.amount,
+                stored.fee,
+                stored.nonce
+            ),
+            (SENDER, RECEIVER, 10, 2, 1)
+        );
+        assert_eq!(stored.status, TransactionStatus::Pending);
+        assert_eq!(stored.public_key, SENDER_KEY);
+        assert_eq!(
+            stored.hash,
+            hex::encode(crypto_helper::transaction_hash(&stored))
+        );
+        // the pending nonce 0 costs 1, this one 12, out of 100
+        let mut pending = vec![stored.clone()];
+        pending[0].nonce = 0;
+        pending[0].amount = 1;
+        pending[0].fee = 0;
+        pending.push(stored);
+        assert_eq!(available_balance(100, &pending), 87);
+    }
+
+    #[tokio::test]
+    async fn create_transaction_registers_unknown_receiver_test() {
+        let stored = Arc::new(Mutex::new(None));
+        let usecase = usecase_with(0, vec![], true, |addresses, transactions| {
+            let mut sequence = Sequence::new();
+            expect_stored(transactions, Arc::clone(&stored), &mut sequence);
+            addresses
+                .expect_insert()
+                .withf(|receiver| {
+                    receiver.address == UNKNOWN
+                        && receiver.public_key.is_none()
+                        && receiver.balance == 0
+                        && receiver.nonce == 0
+                })
+                .times(1)
+                .in_sequence(&mut sequence)
+                .returning(|_| Box::pin(async { Ok(ObjectId::new()) }));
+        });
+
+        let result = usecase.create_transaction(signed_request(0, UNKNOWN)).await;
+        assert!(result.is_ok());
+
+        let stored = stored.lock().unwrap().clone().unwrap();
+        assert_eq!((stored.to.as_str(), stored.amount), (UNKNOWN, 10));
+    }
+
+    #[tokio::test]
+    async fn create_transaction_insert_failure_keeps_receiver_unknown_test() {
+        let usecase = usecase_with(0, vec![], true, |addresses, transactions| {
+            transactions
+                .expect_find_all_pending()
+                .returning(|| Box::pin(async { Ok(vec![]) }));
+            transactions
+                .expect_insert_with_session()
+                .returning(|_, _| Box::pin(async { Err(String::from("write failed")) }));
+            addresses.expect_insert().never();
+        });
+
+        let result = usecase.create_transaction(signed_request(0, UNKNOWN)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn create_transaction_reused_nonce_test() {
+        let result = usecase(3, vec![]).create_transaction(request(2)).await;
+
+        let error = match result {
+            Ok(_) => panic!("reused nonce accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, "nonce 2 was already used, expected 3");
+    }
+
+    #[tokio::test]
+    async fn create_transaction_pending_nonce_test() {
+        let result = usecase(3, vec![3, 4]).create_transaction(request(4)).await;
+
+        let error = match result {
+            Ok(_) => panic!("pending nonce accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, "nonce 4 was already used, expected 5");
+    }
+
+    #[tokio::test]
+    async fn create_transaction_out_of_order_nonce_test() {
+        let result = usecase(0, vec![]).create_transaction(request(1)).await;
+
+        let error = match result {
+            Ok(_) => panic!("out of order nonce accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, "nonce 1 is out of order, expected 0");
+    }
+
+    #[tokio::test]
+    async fn create_transaction_nonce_gap_test() {
+        // nonce 3 expired, 4 and 5 wait for it to be sent again
+        let result = usecase(3, vec![4, 5]).create_transaction(request(6)).await;
+
+        let error = match result {
+            Ok(_) => panic!("nonce after a gap accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, "nonce 6 is out of order, expected 3");
+    }
+
+    fn pending_nonce(nonce: u64, timestamp: i64) -> TransactionEntity {
+        let payload = TransactionPayload {
+            chain_id: 1,
+            from: String::from(SENDER),
+            to: String::from(RECEIVER),
+            amount: 1,
+            fee: 1,
+            nonce,
+            valid_until: None,
+            scheme: SignatureScheme::Ecdsa,
+        };
+        let mut tx = TransactionEntity::new(
+            &payload,
+            String::new(),
+            String::new(),
+            TransactionStatus::Pending,
+            TimerHelper::Mock.creation(),
+        );
+        tx.timestamp = timestamp;
+        return tx;
+    }
+
+    #[test]
+    fn next_nonce_test() {
+        let pending = vec![pending_nonce(3, 100), pending_nonce(4, 100)];
+
+        assert_eq!(next_nonce(3, &pending, 100, 60), 5);
+        assert_eq!(next_nonce(3, &[], 100, 60), 3);
+    }
+
+    #[test]
+    fn next_nonce_skips_expired_test() {
+        // nonce 4 outlived the ttl, the node expects it again
+        let pending = vec![pending_nonce(3, 100), pending_nonce(4, 10)];
+
+        assert_eq!(next_nonce(3, &pending, 100, 60), 4);
+    }
+
+    #[test]
+    fn next_nonce_stops_at_gap_test() {
+        // nonce 4 was evicted, 5 waits for it
+        let pending = vec![pending_nonce(3, 100), pending_nonce(5, 100)];
+
+        assert_eq!(next_nonce(3, &pending, 100, 60), 4);
+    }
+
+    #[tokio::test]
+    async fn create_transaction_fee_exceeds_balance_test() {
+        let mut req = request(0);
+        req.fee = 100;
+        let result = usecase(0, vec![]).create_transaction(req).await;
+
+        let error = match result {
+            Ok(_) => panic!("fee above balance accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(
+            error,
+            format!(
+                "balance is not enough from sender {} need to send 101 but have 100",
+                SENDER
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn create_transaction_expired_test() {
+        let mut req = request(0);
+        req.valid_until = Some(0);
+        let result = usecase(0, vec![]).create_transaction(req).await;
+
+        let error = match result {
+            Ok(_) => panic!("expired transaction accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, "transaction expired at 0, current time is 0");
+    }
+
+    #[tokio::test]
+    async fn create_transaction_pending_spend_test() {
+        let mut req = request(2);
+        req.amount = 99;
+        let result = usecase(0, vec![0, 1]).create_transaction(req).await;
+
+        let error = match result {
+            Ok(_) => panic!("overdraft over pending spends accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(
+            error,
+            format!(
+                "balance is not enough from sender {} need to send 99 but have 98",
+                SENDER
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn create_transaction_zero_amount_test() {
+        let mut req = request(0);
+        req.amount = 0;
+        let result = usecase(0, vec![]).create_transaction(req).await;
+
+        let error = match result {
+            Ok(_) => panic!("zero amount accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, "amount must be greater than zero");
+    }
+
+    #[tokio::test]
+    async fn create_transaction_self_transfer_test() {
+        let mut req = request(0);
+        req.to = String::from(SENDER);
+        let result = usecase(0, vec![]).create_transaction(req).await;
+
+        let error = match result {
+            Ok(_) => panic!("self transfer accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, format!("address {} cannot send to itself", SENDER));
+    }
+
+    #[tokio::test]
+    async fn create_transaction_malformed_receiver_test() {
+        // a public key is not an address
+        let mut req = request(0);
+        req.to = String::from(SENDER_KEY);
+        let result = usecase(0, vec![]).create_transaction(req.clone()).await;
+
+        let error = match result {
+            Ok(_) => panic!("malformed receiver accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, format!("receiver {} is not a valid address", req.to));
+    }
+
+    #[tokio::test]
+    async fn create_transaction_unknown_receiver_test() {
+        let mut req = request(0);
+        req.to = String::from(UNKNOWN);
+        let result = usecase(0, vec![]).create_transaction(req).await;
+
+        let error = match result {
+            Ok(_) => panic!("unknown receiver accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(error, format!("not found address: {}", UNKNOWN));
+    }
+
+    #[tokio::test]
+    async fn create_transaction_foreign_public_key_test() {
+        let mut req = request(0);
+        req.public_key =
+            String::from("02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9");
+        let result = usecase(0, vec![]).create_transaction(req).await;
+
+        let error = match result {
+            Ok(_) => panic!("public key of another address accepted"),
+            Err(e) => e.error().error,
+        };
+        assert_eq!(
+            error,
+            format!("public key does not belong to address {}", SENDER)
+        );
+    }
+}
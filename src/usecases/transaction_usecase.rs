@@ -1,37 +1,61 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use crate::{
     crypto_helper,
-    entities::transaction_entity::{TransactionEntity, TransactionStatus},
+    entities::{
+        address_entity::AddressEntity,
+        transaction_entity::{TransactionEntity, TransactionStatus},
+    },
     errors::{
         address_error::APIAddressError, error::IntoErrorResponse,
         transaction_error::APITransactionError,
     },
-    models::transaction_model::CreateTransactionRequest,
+    models::transaction_model::{CreateTransactionRequest, TransactionPayload},
     repository::{
         address_repository::SharedAddressRepository,
+        session::{BoxedSession, SharedSessionFactory},
         transaction_repository::SharedTransactionRepository,
     },
+    setting::Setting,
     timer_helper::IntoTimerHelperShared,
+    usecases::mempool_usecase::{MempoolUsecase, is_expired},
 };
 use bson::oid::ObjectId;
+use tokio::sync::Mutex;
+use tracing::{error, warn};
 
 pub struct TransactionUsecase {
     tx_repo: SharedTransactionRepository,
     addr_repo: SharedAddressRepository,
+    mempool: Arc<MempoolUsecase>,
+    session_factory: SharedSessionFactory,
+    setting: Arc<Setting>,
     timer_helper: IntoTimerHelperShared,
+    /// Held from reading the sender's balance and pending transactions to
+    /// committing the new one, so two submissions can't both spend the same
+    /// available balance or nonce, or both take the last mempool slot. A
+    /// session alone doesn't prevent that, the two only read what the other
+    /// inserts.
+    submission: Mutex<()>,
 }
 
 impl TransactionUsecase {
     pub fn creation(
         tx_repo: SharedTransactionRepository,
         addr_repo: SharedAddressRepository,
+        mempool: Arc<MempoolUsecase>,
+        session_factory: SharedSessionFactory,
+        setting: Arc<Setting>,
         timer_helper: IntoTimerHelperShared,
     ) -> Arc<Self> {
         return Arc::new(Self {
             tx_repo,
             addr_repo,
+            mempool,
+            session_factory,
+            setting,
             timer_helper,
+            submission: Mutex::new(()),
         });
     }
 
@@ -39,7 +63,7 @@ impl TransactionUsecase {
         &self,
         tx_id: ObjectId,
     ) -> Result<TransactionEntity, Box<dyn IntoErrorResponse>> {
-        return match self.tx_repo.find_by_id(tx_id.clone()).await {
+        return match self.tx_repo.find_by_id(tx_id).await {
             Ok(Some(tx)) => Ok(tx),
             Ok(None) => Err(Box::new(APITransactionError::NotFound(tx_id))),
             Err(e) => Err(Box::new(APITransactionError::FindError(e))),
@@ -62,6 +86,26 @@ impl TransactionUsecase {
         };
     }
 
+    pub async fn get_by_sender(
+        &self,
+        from: String,
+    ) -> Result<Vec<TransactionEntity>, Box<dyn IntoErrorResponse>> {
+        return match self.tx_repo.find_by_address(from.clone()).await {
+            Ok(txs) => Ok(txs.into_iter().filter(|tx| tx.from == from).collect()),
+            Err(e) => Err(Box::new(APITransactionError::FindError(e))),
+        };
+    }
+
+    pub async fn get_by_block_hash(
+        &self,
+        block_hash: String,
+    ) -> Result<Vec<TransactionEntity>, Box<dyn IntoErrorResponse>> {
+        return match self.tx_repo.find_by_block_hash(block_hash).await {
+            Ok(txs) => Ok(txs),
+            Err(e) => Err(Box::new(APITransactionError::FindError(e))),
+        };
+    }
+
     pub async fn get_all_pending(
         &self,
     ) -> Result<Vec<TransactionEntity>, Box<dyn IntoErrorResponse>> {
@@ -76,7 +120,7 @@ impl TransactionUsecase {
         tx_id: ObjectId,
         block_hash: String,
     ) -> Result<(), Box<dyn IntoErrorResponse>> {
-        return match self.tx_repo.mark_confirmed(tx_id.clone(), block_hash).await {
+        return match self.tx_repo.mark_confirmed(tx_id, block_hash).await {
             Ok(()) => Ok(()),
             Err(e) => Err(Box::new(APITransactionError::UpdateStatusError(e))),
         };
@@ -88,7 +132,64 @@ impl TransactionUsecase {
     ) -> Result<(), Box<dyn IntoErrorResponse>> {
         return match self
             .tx_repo
-            .update_status(tx_id.clone(), TransactionStatus::Rejected)
+            .update_status(tx_id, TransactionStatus::Rejected)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(APITransactionError::UpdateStatusError(e))),
+        };
+    }
+
+    pub async fn insert_transaction_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx: TransactionEntity,
+    ) -> Result<ObjectId, Box<dyn IntoErrorResponse>> {
+        return match self.tx_repo.insert_with_session(session, tx).await {
+            Ok(id) => Ok(id),
+            Err(e) => Err(Box::new(APITransactionError::InsertTransactionError(e))),
+        };
+    }
+
+    pub async fn confirm_transaction_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+        block_hash: String,
+    ) -> Result<(), Box<dyn IntoErrorResponse>> {
+        return match self
+            .tx_repo
+            .mark_confirmed_with_session(session, tx_id, block_hash)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(APITransactionError::UpdateStatusError(e))),
+        };
+    }
+
+    pub async fn reject_transaction_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+    ) -> Result<(), Box<dyn IntoErrorResponse>> {
+        return match self
+            .tx_repo
+            .update_status_with_session(session, tx_id, TransactionStatus::Rejected)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(APITransactionError::UpdateStatusError(e))),
+        };
+    }
+
+    pub async fn invalidate_transaction_with_session(
+        &self,
+        session: &mut BoxedSession,
+        tx_id: ObjectId,
+    ) -> Result<(), Box<dyn IntoErrorResponse>> {
+        return match self
+            .tx_repo
+            .update_status_with_session(session, tx_id, TransactionStatus::Invalid)
             .await
         {
             Ok(()) => Ok(()),
@@ -100,29 +201,90 @@ impl TransactionUsecase {
         &self,
         req: CreateTransactionRequest,
     ) -> Result<ObjectId, Box<dyn IntoErrorResponse>> {
+        let now = self.timer_helper.now();
+        if let Some(valid_until) = req.valid_until
+            && valid_until <= now
+        {
+            return Err(Box::new(APITransactionError::Expired(valid_until, now)));
+        }
+
+        if req.amount == 0 {
+            return Err(Box::new(APITransactionError::ZeroAmount));
+        }
+        if req.from == req.to {
+            return Err(Box::new(APITransactionError::SelfTransfer(req.from)));
+        }
+        if !crypto_helper::is_valid_address(&req.to) {
+            return Err(Box::new(APITransactionError::InvalidReceiver(req.to)));
+        }
+        match crypto_helper::address_from_public_key(&req.public_key) {
+            Ok(address) if address == req.from => {}
+            _ => return Err(Box::new(APITransactionError::PublicKeyMismatch(req.from))),
+        };
+
+        let _submission = self.submission.lock().await;
         let sender = match self.addr_repo.get_by_address(req.from.clone()).await {
             Ok(Some(sender)) => sender,
             Ok(None) => return Err(Box::new(APIAddressError::AddressNotFound(req.from))),
             Err(e) => return Err(Box::new(APIAddressError::FindAddressError(e))),
         };
-        let _receiver: crate::entities::address_entity::AddressEntity =
-            match self.addr_repo.get_by_address(req.from.clone()).await {
-                Ok(Some(recv)) => recv,
-                Ok(None) => return Err(Box::new(APIAddressError::AddressNotFound(req.from))),
-                Err(e) => return Err(Box::new(APIAddressError::FindAddressError(e))),
-            };
+        let receiver_exists = match self.addr_repo.get_by_address(req.to.clone()).await {
+            Ok(receiver) => receiver.is_some(),
+            Err(e) => return Err(Box::new(APIAddressError::FindAddressError(e))),
+        };
+        if !receiver_exists && !self.setting.transaction.create_unknown_receiver {
+            return Err(Box::new(APIAddressError::AddressNotFound(req.to)));
+        }
 
-        if sender.balance < req.amount {
+        let pending = match self
+            .tx_repo
+            .find_pending_by_sender(sender.address.clone())
+            .await
+        {
+            Ok(txs) => txs,
+            Err(e) => return Err(Box::new(APITransactionError::FindError(e))),
+        };
+
+        let cost = req.amount.saturating_add(req.fee);
+        let available = available_balance(sender.balance, &pending);
+        if available < cost {
             return Err(Box::new(APITransactionError::BalanceNotEnough(
-                req.from,
-                sender.balance,
-                req.amount,
+                req.from, available, cost,
+            )));
+        }
+
+        let expected_nonce = next_nonce(
+            sender.nonce,
+            &pending,
+            now,
+            self.setting.mempool.transaction_ttl,
+        );
+        if req.nonce < expected_nonce {
+            return Err(Box::new(APITransactionError::NonceTooLow(
+                expected_nonce,
+                req.nonce,
+            )));
+        }
+        if req.nonce > expected_nonce {
+            return Err(Box::new(APITransactionError::NonceTooHigh(
+                expected_nonce,
+                req.nonce,
             )));
         }
 
-        let message = format!("{}{}{}", req.from, req.to, req.amount);
+        let payload = TransactionPayload {
+            chain_id: self.setting.chain.id,
+            from: req.from.clone(),
+            to: req.to.clone(),
+            amount: req.amount,
+            fee: req.fee,
+            nonce: req.nonce,
+            valid_until: req.valid_until,
+            scheme: req.scheme,
+        };
 
-        let verify_result = crypto_helper::verify_signature(&message, &req.from, &req.signature);
+        let verify_result =
+            crypto_helper::verify_signature(&payload, &req.public_key, &req.signature);
         let is_valid = match verify_result {
             Ok(r) => r,
             Err(e) => {
@@ -137,18 +299,91 @@ impl TransactionUsecase {
         }
 
         let new_transaction = TransactionEntity::new(
-            req.from.clone(),
-            req.to.clone(),
-            req.amount,
+            &payload,
+            req.public_key.clone(),
             req.signature.clone(),
             TransactionStatus::Pending,
             Arc::clone(&self.timer_helper),
         );
 
-        return match self.tx_repo.insert(new_transaction).await {
-            Ok(id) => Ok(id),
-            Err(e) => Err(Box::new(APITransactionError::InsertTransactionError(e))),
+        let tx_id = self.insert_pending(new_transaction).await?;
+
+        // registered only once the transaction is stored, so a failed insert
+        // leaves no orphan address. Should this insert fail, the receiver was
+        // registered meanwhile or the block builder rejects the transaction.
+        if !receiver_exists {
+            let receiver = AddressEntity::new(req.to.clone(), None, Arc::clone(&self.timer_helper));
+            if let Err(e) = self.addr_repo.insert(receiver).await {
+                warn!("register receiver {} of {} failed: {}", req.to, tx_id, e);
+            }
+        }
+
+        return Ok(tx_id);
+    }
+
+    /// Admits a checked transaction into the mempool and stores it in one
+    /// session, committed only once both succeeded, so an eviction is
+    /// undone when the insert fails.
+    async fn insert_pending(
+        &self,
+        tx: TransactionEntity,
+    ) -> Result<ObjectId, Box<dyn IntoErrorResponse>> {
+        let mut session = match self.session_factory.start().await {
+            Ok(session) => session,
+            Err(e) => return Err(Box::new(APITransactionError::SessionError(e))),
+        };
+
+        let admitted = self
+            .mempool
+            .admit(&mut session, &tx)
+            .await
+            .map_err(|e| e.error());
+        let inserted = match admitted {
+            Ok(()) => match self.tx_repo.insert_with_session(&mut session, tx).await {
+                Ok(tx_id) => Ok(tx_id),
+                Err(e) => Err(APITransactionError::InsertTransactionError(e).error()),
+            },
+            Err(response) => Err(response),
+        };
+
+        return match inserted {
+            Ok(tx_id) => match session.commit().await {
+                Ok(()) => Ok(tx_id),
+                Err(e) => Err(Box::new(APITransactionError::SessionError(e))),
+            },
+            Err(response) => {
+                if let Err(e) = session.abort().await {
+                    error!("insert_pending: abort failed: {}", e);
+                }
+                Err(Box::new(response))
+            }
         };
     }
+}
+
+/// The balance left for new transactions once the sender's pending
+/// transactions, amount and fee, are paid.
+pub fn available_balance(balance: u64, pending: &[TransactionEntity]) -> u64 {
+    let outflow = pending.iter().fold(0u64, |sum, tx| {
+        sum.saturating_add(tx.amount).saturating_add(tx.fee)
+    });
+    return balance.saturating_sub(outflow);
+}
+
+/// The nonce a new transaction must carry: the account nonce advanced past
+/// the sender's pending transactions that can still be mined. Those after
+/// a gap left by an expired or evicted nonce wait for the gap to be filled,
+/// so they don't count.
+pub fn next_nonce(account_nonce: u64, pending: &[TransactionEntity], now: i64, ttl: i64) -> u64 {
+    let nonces: HashSet<u64> = pending
+        .iter()
+        .filter(|tx| !is_expired(tx, now, ttl))
+        .map(|tx| tx.nonce)
+        .collect();
 
+    let mut next = account_nonce;
+    while nonces.contains(&next) {
+        next += 1;
+    }
+    return next;
 }